@@ -17,6 +17,12 @@ pub enum Expr {
         name: Token,
         value: Box<Expr>,
     },
+    // `x += 1` and friends, `op` is the compound token itself
+    CompoundAssign {
+        name: Token,
+        op: Token,
+        value: Box<Expr>,
+    },
     Binary {
         left: Box<Expr>,
         op: Token,
@@ -27,6 +33,16 @@ pub enum Expr {
         paren: Token,
         arguments: Vec<Expr>,
     },
+    // `object.name`, or `object?.name` which gives nil when the object is nil
+    Get {
+        object: Box<Expr>,
+        name: Token,
+        optional: bool,
+    },
+    // a chain of gets and calls holding a `?.`, a nil met at one skips the rest of the chain
+    OptionalChain {
+        expr: Box<Expr>,
+    },
     Grouping {
         expr: Box<Expr>,
     },
@@ -38,6 +54,11 @@ pub enum Expr {
         op: Token,
         right: Box<Expr>,
     },
+    Ternary {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
     Unary {
         op: Token,
         expr: Box<Expr>,
@@ -50,13 +71,63 @@ pub enum Expr {
 #[derive(Debug, PartialEq, Clone)]
 pub enum Stmt {
     Block(Vec<Stmt>),
-    Class(Token, Vec<Stmt>),
+    Class(Token, Vec<Stmt>, Option<String>),
     Expr(Expr),
-    Function(Token, Vec<Token>, Vec<Stmt>),
+    For(Option<Box<Stmt>>, Option<Expr>, Option<Expr>, Box<Stmt>),
+    Function(Token, Vec<Token>, Vec<Stmt>, Option<String>),
     IfStmt(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    // `import "path" as name;` or `import { a, b } from "path";`, the token is the path string,
+    // no alias and no names binds the whole module under the file stem
+    Import(Token, Option<Token>, Vec<Token>),
     Print(Expr),
     Return(Token, Option<Expr>),
+    // `test "name" { ... }`, the token is the name string
+    Test(Token, Vec<Stmt>),
+    Throw(Token, Expr),
+    // `try` keyword, body, `catch (name) { }` and `finally { }`, at least one of the two is present
+    Try(Token, Vec<Stmt>, Option<(Token, Vec<Stmt>)>, Option<Vec<Stmt>>),
     VarDeclaration(Token, Option<Expr>),
     While(Expr, Box<Stmt>),
     Null,
 }
+
+impl Expr {
+    //the line the expression starts on, or at least one of its tokens is on
+    pub fn line(&self) -> u32 {
+        match self {
+            Expr::Assign { name, .. } | Expr::CompoundAssign { name, .. } => name.line,
+            Expr::Binary { left, .. } | Expr::Logical { left, .. } => left.line(),
+            Expr::Call { callee, .. } => callee.line(),
+            Expr::Get { object, .. } => object.line(),
+            Expr::Grouping { expr } | Expr::OptionalChain { expr } => expr.line(),
+            Expr::Literal { token } => token.line,
+            Expr::Ternary { condition, .. } => condition.line(),
+            Expr::Unary { op, .. } => op.line,
+            Expr::Variable { name } => name.line,
+        }
+    }
+}
+
+impl Stmt {
+    //the line a debugger stops at for this statement, blocks only stop at what is inside them
+    pub fn line(&self) -> Option<u32> {
+        match self {
+            Stmt::Block(_) | Stmt::Null => None,
+            Stmt::Class(name, ..) | Stmt::Function(name, ..) => Some(name.line),
+            Stmt::Expr(expr) | Stmt::Print(expr) => Some(expr.line()),
+            Stmt::For(initializer, condition, increment, body) => initializer
+                .as_ref()
+                .and_then(|stmt| stmt.line())
+                .or_else(|| condition.as_ref().map(Expr::line))
+                .or_else(|| increment.as_ref().map(Expr::line))
+                .or_else(|| body.line()),
+            Stmt::IfStmt(condition, ..) | Stmt::While(condition, _) => Some(condition.line()),
+            Stmt::Import(token, ..)
+            | Stmt::Return(token, _)
+            | Stmt::Test(token, _)
+            | Stmt::Throw(token, _)
+            | Stmt::Try(token, ..)
+            | Stmt::VarDeclaration(token, _) => Some(token.line),
+        }
+    }
+}
@@ -30,6 +30,16 @@ impl Visitor for AstPrinter {
             } => {
                 format!("Assignment {:?} {:?}", name, self.visit_expression(value))
             }
+            Expr::CompoundAssign {
+                ref name,
+                ref op,
+                ref value,
+            } => format!(
+                "(CompoundAssignment {:?} {:?} {:?})",
+                op,
+                name,
+                self.visit_expression(value)
+            ),
             Expr::Binary {
                 ref left,
                 ref op,
@@ -58,7 +68,28 @@ impl Visitor for AstPrinter {
                 )
             }
 
+            Expr::Get {
+                ref object,
+                ref name,
+                optional,
+            } => format!(
+                "(Get {:?} {:?} optional: {})",
+                self.visit_expression(object),
+                name,
+                optional
+            ),
+            Expr::Ternary {
+                ref condition,
+                ref then_branch,
+                ref else_branch,
+            } => format!(
+                "(Ternary {:?} {:?} {:?})",
+                self.visit_expression(condition),
+                self.visit_expression(then_branch),
+                self.visit_expression(else_branch)
+            ),
             Expr::Grouping { ref expr, .. } => format!("Group {:?}", self.visit_expression(expr)),
+            Expr::OptionalChain { ref expr } => format!("(Chain {:?})", self.visit_expression(expr)),
             Expr::Literal { ref token, .. } => format!("Literal {:?}", token),
             Expr::Logical {
                 ref left,
@@ -89,7 +120,7 @@ impl Visitor for AstPrinter {
                     .map(|x| self.visit_statement(s))
                     .collect::<String>()
             ),
-            Stmt::Class(ref token, ref function) => {
+            Stmt::Class(ref token, ref function, _) => {
                 format!(
                     "Class Declaration Statement {:?}, methods: {:?}",
                     token,
@@ -102,8 +133,24 @@ impl Visitor for AstPrinter {
             Stmt::Expr(ref expr) => {
                 format!("(Expression Statement {})", self.visit_expression(expr))
             }
+            Stmt::For(ref initializer, ref condition, ref increment, ref body) => format!(
+                "(For Statement {:?} {:?} {:?} {:?})",
+                match initializer {
+                    &Some(ref init) => self.visit_statement(init),
+                    &None => String::from(""),
+                },
+                match condition {
+                    &Some(ref cond) => self.visit_expression(cond),
+                    &None => String::from(""),
+                },
+                match increment {
+                    &Some(ref inc) => self.visit_expression(inc),
+                    &None => String::from(""),
+                },
+                self.visit_statement(body)
+            ),
 
-            Stmt::Function(ref name, ref parameters, ref body) => {
+            Stmt::Function(ref name, ref parameters, ref body, _) => {
                 format!(
                     "(Function Declaration Statement \n\tname: {:?} \n\tparameters: [{}] \n\tbody: {} \n)",
                     name,
@@ -128,6 +175,10 @@ impl Visitor for AstPrinter {
                     }
                 )
             }
+            Stmt::Import(ref path, ref alias, ref names) => format!(
+                "(Import {:?} as: {:?} names: {:?})",
+                path, alias, names
+            ),
             Stmt::Print(ref expr) => {
                 format!("Print Statement {}", self.visit_expression(expr).as_str())
             }
@@ -139,6 +190,40 @@ impl Visitor for AstPrinter {
                     &None => "nil".to_string(),
                 }
             ),
+            Stmt::Test(ref name, ref body) => format!(
+                "(Test {:?} {})",
+                name,
+                body.iter()
+                    .map(|s| self.visit_statement(s))
+                    .collect::<String>()
+            ),
+            Stmt::Throw(ref keyword, ref value) => {
+                format!("(Throw {:?} {})", keyword, self.visit_expression(value))
+            }
+            Stmt::Try(_, ref body, ref catch, ref finally) => format!(
+                "(Try {} catch: {} finally: {})",
+                body.iter()
+                    .map(|s| self.visit_statement(s))
+                    .collect::<String>(),
+                match catch {
+                    &Some((ref name, ref handler)) => format!(
+                        "{:?} {}",
+                        name,
+                        handler
+                            .iter()
+                            .map(|s| self.visit_statement(s))
+                            .collect::<String>()
+                    ),
+                    &None => String::from(""),
+                },
+                match finally {
+                    &Some(ref statements) => statements
+                        .iter()
+                        .map(|s| self.visit_statement(s))
+                        .collect::<String>(),
+                    &None => String::from(""),
+                }
+            ),
 
             Stmt::VarDeclaration(ref token, ref expr_opt) => format!(
                 "Variable Declaration {:?} {:?}",
@@ -168,18 +253,21 @@ fn test_ast_printer() {
                 t_type: TokenType::Number(1.0),
                 lexeme: "1".to_string(),
                 line: 1,
+                column: 0,
             },
         }),
         op: Token {
             t_type: TokenType::Plus,
             lexeme: "+".to_string(),
             line: 1,
+            column: 0,
         },
         right: Box::new(Expr::Literal {
             token: Token {
                 t_type: TokenType::Number(2.0),
                 lexeme: "2".to_string(),
                 line: 1,
+                column: 0,
             },
         }),
     };
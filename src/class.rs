@@ -1,11 +1,12 @@
 use std::fmt::{self, Debug};
 pub struct Class {
     name: String,
+    pub doc: Option<String>,
 }
 
 impl Class {
-    pub fn new(name: String) -> Self {
-        Self { name }
+    pub fn new(name: String, doc: Option<String>) -> Self {
+        Self { name, doc }
     }
 }
 
@@ -0,0 +1,772 @@
+//Debugging a script from an editor, `rlox dap` speaks the Debug Adapter Protocol over stdio
+use crate::debugger::{self, DebugHook, Resume, Stepper, StopReason};
+use crate::environment::Environment;
+use crate::error::Error;
+use crate::interpreter::{Capabilities, Interpreter, Types};
+use crate::module;
+use crate::protocol::{self, field, int_field, list, object, string, text_field};
+use crate::stdlib::types::type_name;
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+//a script runs on a single thread, editors still want it to have an id
+const THREAD_ID: i64 = 1;
+
+//the client, shared by the adapter and the script's output
+struct Connection<R, W> {
+    input: R,
+    out: W,
+    seq: i64,
+}
+
+type Shared<R, W> = Rc<RefCell<Connection<R, W>>>;
+
+impl<R: BufRead, W: Write> Connection<R, W> {
+    fn read(&mut self) -> Option<Types> {
+        protocol::read(&mut self.input)
+    }
+
+    fn send(&mut self, kind: &str, fields: Vec<(&str, Types)>) {
+        self.seq += 1;
+        let mut message = vec![("seq", Types::Int(self.seq)), ("type", string(kind))];
+        message.extend(fields);
+        protocol::write(&mut self.out, &object(message));
+    }
+
+    fn respond(&mut self, request: &Types, body: Types) {
+        self.send(
+            "response",
+            vec![
+                (
+                    "request_seq",
+                    field(request, "seq").unwrap_or(Types::Int(0)),
+                ),
+                ("success", Types::Boolean(true)),
+                ("command", field(request, "command").unwrap_or(Types::Nil)),
+                ("body", body),
+            ],
+        );
+    }
+
+    fn fail(&mut self, request: &Types, message: &str) {
+        self.send(
+            "response",
+            vec![
+                (
+                    "request_seq",
+                    field(request, "seq").unwrap_or(Types::Int(0)),
+                ),
+                ("success", Types::Boolean(false)),
+                ("command", field(request, "command").unwrap_or(Types::Nil)),
+                ("message", string(message)),
+            ],
+        );
+    }
+
+    fn event(&mut self, event: &str, body: Types) {
+        self.send("event", vec![("event", string(event)), ("body", body)]);
+    }
+}
+
+fn command(request: &Types) -> String {
+    text_field(request, "command").unwrap_or_default()
+}
+
+fn arguments(request: &Types) -> Types {
+    field(request, "arguments").unwrap_or(Types::Nil)
+}
+
+//`setBreakpoints` replaces every breakpoint of one source file
+fn set_breakpoints<R: BufRead, W: Write>(
+    connection: &Shared<R, W>,
+    stepper: &mut Stepper,
+    request: &Types,
+) {
+    let arguments = arguments(request);
+    let path = field(&arguments, "source").and_then(|source| text_field(&source, "path"));
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            return connection
+                .borrow_mut()
+                .fail(request, "A source path is required.")
+        }
+    };
+    let lines: Vec<u32> = match field(&arguments, "breakpoints") {
+        Some(Types::List(breakpoints)) => breakpoints
+            .borrow()
+            .iter()
+            .filter_map(|b| int_field(b, "line"))
+            .filter(|line| *line > 0)
+            .map(|line| line as u32)
+            .collect(),
+        _ => Vec::new(),
+    };
+    stepper.set_breakpoints(&path, &lines);
+    let verified = lines
+        .iter()
+        .map(|line| {
+            object(vec![
+                ("verified", Types::Boolean(true)),
+                ("line", Types::Int(*line as i64)),
+            ])
+        })
+        .collect();
+    connection
+        .borrow_mut()
+        .respond(request, object(vec![("breakpoints", list(verified))]));
+}
+
+fn threads<R: BufRead, W: Write>(connection: &Shared<R, W>, request: &Types) {
+    let thread = object(vec![
+        ("id", Types::Int(THREAD_ID)),
+        ("name", string("main")),
+    ]);
+    connection
+        .borrow_mut()
+        .respond(request, object(vec![("threads", list(vec![thread]))]));
+}
+
+//what `launch` asked for
+struct Launch {
+    program: PathBuf,
+    source: String,
+    args: Vec<String>,
+    stop_on_entry: bool,
+}
+
+fn launch(request: &Types) -> Result<Launch, String> {
+    let arguments = arguments(request);
+    let program = text_field(&arguments, "program").ok_or("A program to debug is required.")?;
+    let source = fs::read_to_string(&program).map_err(|e| format!("{}: {}", program, e))?;
+    let args = match field(&arguments, "args") {
+        Some(Types::List(args)) => args
+            .borrow()
+            .iter()
+            .filter_map(|arg| match arg {
+                Types::ReturnString(arg) => Some(arg.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    Ok(Launch {
+        program: PathBuf::from(program),
+        source,
+        args,
+        stop_on_entry: std::matches!(field(&arguments, "stopOnEntry"), Some(Types::Boolean(true))),
+    })
+}
+
+//Serves one debugging session, returns the exit status of the script.
+//Breakpoints set before `configurationDone` are in place when the script starts.
+pub fn serve<R, W>(input: R, out: W, capabilities: Capabilities) -> i32
+where
+    R: BufRead + 'static,
+    W: Write + 'static,
+{
+    let connection = Rc::new(RefCell::new(Connection { input, out, seq: 0 }));
+    let mut stepper = Stepper::new();
+    let mut launched = None;
+    let mut configured = false;
+
+    while launched.is_none() || !configured {
+        let request = match connection.borrow_mut().read() {
+            Some(request) => request,
+            None => return 0,
+        };
+        match command(&request).as_str() {
+            "initialize" => {
+                let capabilities = object(vec![
+                    ("supportsConfigurationDoneRequest", Types::Boolean(true)),
+                    ("supportsEvaluateForHovers", Types::Boolean(true)),
+                ]);
+                let mut connection = connection.borrow_mut();
+                connection.respond(&request, capabilities);
+                connection.event("initialized", Types::Nil);
+            }
+            "launch" => match launch(&request) {
+                Ok(launch) => {
+                    connection.borrow_mut().respond(&request, Types::Nil);
+                    launched = Some(launch);
+                }
+                Err(message) => connection.borrow_mut().fail(&request, &message),
+            },
+            "setBreakpoints" => set_breakpoints(&connection, &mut stepper, &request),
+            "setExceptionBreakpoints" => connection.borrow_mut().respond(&request, Types::Nil),
+            "threads" => threads(&connection, &request),
+            "configurationDone" => {
+                connection.borrow_mut().respond(&request, Types::Nil);
+                configured = true;
+            }
+            "disconnect" => {
+                connection.borrow_mut().respond(&request, Types::Nil);
+                return 0;
+            }
+            other => connection
+                .borrow_mut()
+                .fail(&request, &format!("Unsupported request '{}'.", other)),
+        }
+    }
+
+    let launch = launched.expect("the loop above waits for a launch");
+    if !launch.stop_on_entry {
+        stepper.resume(Resume::Continue);
+    }
+    let sink = OutputSink {
+        connection: connection.clone(),
+        pending: Vec::new(),
+    };
+    let mut interpreter = Interpreter::with_output(Box::new(sink));
+    interpreter.capabilities = capabilities;
+    interpreter.set_args(launch.args);
+    interpreter.set_script_path(&launch.program);
+    interpreter.set_debugger(Box::new(Adapter {
+        connection: connection.clone(),
+        stepper,
+        entry: launch.stop_on_entry,
+        variables: Vec::new(),
+    }));
+    let code = crate::run_with(&mut interpreter, launch.source.as_bytes()).code();
+    let _ = interpreter.output().flush();
+    drop(interpreter);
+
+    let mut shared = connection.borrow_mut();
+    shared.event(
+        "exited",
+        object(vec![("exitCode", Types::Int(code as i64))]),
+    );
+    shared.event("terminated", Types::Nil);
+    while let Some(request) = shared.read() {
+        if command(&request) == "disconnect" {
+            shared.respond(&request, Types::Nil);
+            break;
+        }
+        shared.fail(&request, "The script has finished.");
+    }
+    code
+}
+
+//sends what the script prints to the client as output events, a line at a time
+struct OutputSink<R, W> {
+    connection: Shared<R, W>,
+    pending: Vec<u8>,
+}
+
+impl<R: BufRead, W: Write> OutputSink<R, W> {
+    fn send(&mut self, text: Vec<u8>) {
+        let body = object(vec![
+            ("category", string("stdout")),
+            ("output", string(&String::from_utf8_lossy(&text))),
+        ]);
+        self.connection.borrow_mut().event("output", body);
+    }
+}
+
+impl<R: BufRead, W: Write> Write for OutputSink<R, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        if let Some(end) = self.pending.iter().rposition(|b| *b == b'\n') {
+            let rest = self.pending.split_off(end + 1);
+            let lines = std::mem::replace(&mut self.pending, rest);
+            self.send(lines);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            let text = std::mem::take(&mut self.pending);
+            self.send(text);
+        }
+        Ok(())
+    }
+}
+
+//what a `variablesReference` handed to the client expands to, they are only valid while stopped
+enum Variables {
+    // the variables of these scopes, innermost first, shadowed names are shown once
+    Scopes(Vec<Environment>),
+    // the items of a list or the entries of a map
+    Value(Types),
+}
+
+//the hook the interpreter calls before each statement while an editor is attached
+struct Adapter<R, W> {
+    connection: Shared<R, W>,
+    stepper: Stepper,
+    // the first stop of a session started with `stopOnEntry` is reported as the entry
+    entry: bool,
+    variables: Vec<Variables>,
+}
+
+impl<R: BufRead, W: Write> Adapter<R, W> {
+    fn respond(&self, request: &Types, body: Types) {
+        self.connection.borrow_mut().respond(request, body);
+    }
+
+    fn fail(&self, request: &Types, message: &str) {
+        self.connection.borrow_mut().fail(request, message);
+    }
+
+    //a handle for the client to expand later, 0 means there is nothing to expand
+    fn reference(&mut self, variables: Variables) -> i64 {
+        self.variables.push(variables);
+        self.variables.len() as i64
+    }
+
+    fn value_reference(&mut self, value: &Types) -> i64 {
+        match value {
+            Types::List(_) | Types::Map(_) => self.reference(Variables::Value(value.clone())),
+            _ => 0,
+        }
+    }
+
+    //the frame a request names, frames are numbered from the outermost call
+    fn frame(interpreter: &Interpreter, arguments: &Types) -> Option<(usize, Environment)> {
+        let index = match int_field(arguments, "frameId") {
+            Some(id) => usize::try_from(id).ok()?,
+            None => interpreter.call_depth(),
+        };
+        let env = interpreter.frame_environment(index)?.clone();
+        Some((index, env))
+    }
+
+    fn stack_trace(&self, interpreter: &Interpreter, request: &Types) {
+        let frames: Vec<Types> = interpreter
+            .call_stack()
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(id, frame)| {
+                let mut fields = vec![
+                    ("id", Types::Int(id as i64)),
+                    ("name", string(&frame.name)),
+                    ("line", Types::Int(frame.line as i64)),
+                    ("column", Types::Int(1)),
+                ];
+                if let Some(file) = &frame.file {
+                    fields.push((
+                        "source",
+                        object(vec![
+                            ("name", string(&module::display_name(file))),
+                            ("path", string(&file.display().to_string())),
+                        ]),
+                    ));
+                }
+                object(fields)
+            })
+            .collect();
+        let total = Types::Int(frames.len() as i64);
+        self.respond(
+            request,
+            object(vec![("stackFrames", list(frames)), ("totalFrames", total)]),
+        );
+    }
+
+    //the block and function scopes of a frame as its locals, the top level it runs in as globals
+    fn scopes(&mut self, interpreter: &Interpreter, request: &Types) {
+        let env = match Self::frame(interpreter, &arguments(request)) {
+            Some((_, env)) => env,
+            None => return self.fail(request, "Unknown frame."),
+        };
+        let mut locals = Vec::new();
+        let mut current = Some(&env);
+        while let Some(scope) = current.filter(|scope| !scope.is_top_level()) {
+            locals.push(scope.clone());
+            current = scope.enclosing();
+        }
+        let mut scopes = Vec::new();
+        let top_level = current.cloned();
+        if !locals.is_empty() {
+            let reference = self.reference(Variables::Scopes(locals));
+            scopes.push(scope("Locals", reference));
+        }
+        if let Some(top_level) = top_level {
+            let reference = self.reference(Variables::Scopes(vec![top_level]));
+            scopes.push(scope("Globals", reference));
+        }
+        self.respond(request, object(vec![("scopes", list(scopes))]));
+    }
+
+    fn variables(&mut self, interpreter: &Interpreter, request: &Types) {
+        let reference = int_field(&arguments(request), "variablesReference").unwrap_or(0);
+        let index = usize::try_from(reference - 1).ok();
+        let entries: Vec<(String, Types)> = match index.and_then(|i| self.variables.get(i)) {
+            Some(Variables::Scopes(scopes)) => {
+                let mut entries: Vec<(String, Types)> = Vec::new();
+                for scope in scopes {
+                    let mut names = Vec::new();
+                    scope.for_each(|name, value| {
+                        // the natives every script starts with would bury the script's own globals
+                        let native =
+                            std::matches!(value, Types::Callable(f) if f.closure().is_none());
+                        if !native && !entries.iter().any(|(seen, _)| seen == name) {
+                            names.push((name.to_string(), value.clone()));
+                        }
+                    });
+                    names.sort_by(|a, b| a.0.cmp(&b.0));
+                    entries.extend(names);
+                }
+                entries
+            }
+            Some(Variables::Value(Types::List(items))) => items
+                .borrow()
+                .iter()
+                .enumerate()
+                .map(|(i, item)| (i.to_string(), item.clone()))
+                .collect(),
+            Some(Variables::Value(Types::Map(map))) => map
+                .borrow()
+                .iter()
+                .map(|(key, value)| match key {
+                    Types::ReturnString(key) => (key.clone(), value.clone()),
+                    other => (other.to_string(), value.clone()),
+                })
+                .collect(),
+            Some(Variables::Value(_)) => Vec::new(),
+            None => return self.fail(request, "Unknown variables reference."),
+        };
+        let variables = entries
+            .into_iter()
+            .map(|(name, value)| {
+                let reference = self.value_reference(&value);
+                object(vec![
+                    ("name", string(&name)),
+                    ("value", string(&interpreter.stringify(value.clone()))),
+                    ("type", string(type_name(&value))),
+                    ("variablesReference", Types::Int(reference)),
+                ])
+            })
+            .collect();
+        self.respond(request, object(vec![("variables", list(variables))]));
+    }
+
+    fn evaluate(&mut self, interpreter: &mut Interpreter, request: &Types) {
+        let arguments = arguments(request);
+        let env = match Self::frame(interpreter, &arguments) {
+            Some((_, env)) => env,
+            None => return self.fail(request, "Unknown frame."),
+        };
+        let source = text_field(&arguments, "expression").unwrap_or_default();
+        let expr = match debugger::parse_expression(&source) {
+            Some(Ok(expr)) => expr,
+            _ => return self.fail(request, &format!("Could not parse '{}'.", source)),
+        };
+        match interpreter.evaluate_in(&expr, env) {
+            Ok(value) => {
+                let reference = self.value_reference(&value);
+                let body = object(vec![
+                    ("result", string(&interpreter.stringify(value.clone()))),
+                    ("type", string(type_name(&value))),
+                    ("variablesReference", Types::Int(reference)),
+                ]);
+                self.respond(request, body);
+            }
+            Err(error) => self.fail(request, &debugger::error_message(error)),
+        }
+    }
+
+    fn resume(&mut self, request: &Types, resume: Resume) -> Result<(), Error> {
+        self.respond(request, Types::Nil);
+        self.stepper.resume(resume);
+        self.variables.clear();
+        Ok(())
+    }
+
+    //answers requests until one of them resumes the script
+    fn stopped(&mut self, interpreter: &mut Interpreter) -> Result<(), Error> {
+        loop {
+            let request = match self.connection.borrow_mut().read() {
+                Some(request) => request,
+                // the editor went away, there is no one left to debug for
+                None => return Err(Error::Exit { code: 0 }),
+            };
+            let depth = interpreter.call_depth();
+            match command(&request).as_str() {
+                "threads" => threads(&self.connection, &request),
+                "stackTrace" => self.stack_trace(interpreter, &request),
+                "scopes" => self.scopes(interpreter, &request),
+                "variables" => self.variables(interpreter, &request),
+                "evaluate" => self.evaluate(interpreter, &request),
+                "setBreakpoints" => set_breakpoints(&self.connection, &mut self.stepper, &request),
+                "continue" => return self.resume(&request, Resume::Continue),
+                "next" => return self.resume(&request, Resume::Next(depth)),
+                "stepIn" => return self.resume(&request, Resume::Step),
+                "stepOut" => return self.resume(&request, Resume::Finish(depth)),
+                "disconnect" => {
+                    self.respond(&request, Types::Nil);
+                    return Err(Error::Exit { code: 0 });
+                }
+                other => self.fail(&request, &format!("Unsupported request '{}'.", other)),
+            }
+        }
+    }
+}
+
+fn scope(name: &str, reference: i64) -> Types {
+    object(vec![
+        ("name", string(name)),
+        ("variablesReference", Types::Int(reference)),
+        ("expensive", Types::Boolean(false)),
+    ])
+}
+
+impl<R: BufRead, W: Write> DebugHook for Adapter<R, W> {
+    fn statement(&mut self, interpreter: &mut Interpreter, line: u32) -> Result<(), Error> {
+        let reason = match self.stepper.check(interpreter, line) {
+            Some(reason) => reason,
+            None => return Ok(()),
+        };
+        let reason = match reason {
+            StopReason::Step if std::mem::take(&mut self.entry) => "entry",
+            StopReason::Step => "step",
+            StopReason::Breakpoint => "breakpoint",
+        };
+        self.entry = false;
+        let body = object(vec![
+            ("reason", string(reason)),
+            ("threadId", Types::Int(THREAD_ID)),
+            ("allThreadsStopped", Types::Boolean(true)),
+        ]);
+        self.connection.borrow_mut().event("stopped", body);
+        self.stopped(interpreter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::CapturedOutput;
+    use crate::stdlib::json;
+    use std::io::Cursor;
+
+    const SCRIPT: &str = "fun square(n) {
+    var result = n * n;
+    return result;
+}
+var total = 0;
+for (var i = 1; i <= 2; i = i + 1) {
+    total = total + square(i);
+}
+print total;
+";
+
+    //frames each request the way an editor would
+    fn requests(requests: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut input = String::new();
+        for (seq, (command, arguments)) in requests.iter().enumerate() {
+            let body = format!(
+                "{{\"seq\": {}, \"type\": \"request\", \"command\": \"{}\", \"arguments\": {}}}",
+                seq + 1,
+                command,
+                arguments
+            );
+            input.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+        }
+        Cursor::new(input.into_bytes())
+    }
+
+    fn messages(output: &str) -> Vec<Types> {
+        let mut messages = Vec::new();
+        let mut rest = output;
+        while let Some((header, body)) = rest.split_once("\r\n\r\n") {
+            let length: usize = header
+                .trim_start_matches("Content-Length: ")
+                .parse()
+                .unwrap();
+            messages.push(json::parse(&body[..length]).unwrap());
+            rest = &body[length..];
+        }
+        messages
+    }
+
+    //runs SCRIPT from a file under a scripted client, returns the exit code and what was sent back
+    fn session(name: &str, script: &[(&str, &str)]) -> (i32, Vec<Types>) {
+        session_with(name, SCRIPT, script)
+    }
+
+    fn session_with(name: &str, source: &str, script: &[(&str, &str)]) -> (i32, Vec<Types>) {
+        let path =
+            std::env::temp_dir().join(format!("rlox-dap-{}-{}.lox", name, std::process::id()));
+        fs::write(&path, source).unwrap();
+        let path_json = json::stringify(&string(&path.display().to_string()), None).unwrap();
+        let script: Vec<(&str, String)> = script
+            .iter()
+            .map(|(command, arguments)| (*command, arguments.replace("$PATH", &path_json)))
+            .collect();
+        let script: Vec<(&str, &str)> = script.iter().map(|(c, a)| (*c, a.as_str())).collect();
+        let out = CapturedOutput::default();
+        let code = serve(requests(&script), out.clone(), Capabilities::default());
+        fs::remove_file(&path).unwrap();
+        (code, messages(&out.contents()))
+    }
+
+    //"response command" or "event name" for each message, to check the order of the conversation
+    fn outline(messages: &[Types]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|message| {
+                let kind = text_field(message, "type").unwrap();
+                let name = text_field(message, "command").or(text_field(message, "event"));
+                format!("{} {}", kind, name.unwrap())
+            })
+            .collect()
+    }
+
+    fn body(messages: &[Types], index: usize) -> Types {
+        field(&messages[index], "body").unwrap()
+    }
+
+    fn path(value: &Types, keys: &[&str]) -> Types {
+        keys.iter().fold(value.clone(), |value, key| {
+            match (&value, key.parse::<usize>()) {
+                (Types::List(items), Ok(i)) => items.borrow()[i].clone(),
+                _ => field(&value, key).unwrap(),
+            }
+        })
+    }
+
+    #[test]
+    fn test_breakpoint_stack_variables_and_evaluate() {
+        let (code, messages) = session(
+            "inspect",
+            &[
+                ("initialize", "{\"adapterID\": \"rlox\"}"),
+                ("launch", "{\"program\": $PATH}"),
+                (
+                    "setBreakpoints",
+                    "{\"source\": {\"path\": $PATH}, \"breakpoints\": [{\"line\": 2}]}",
+                ),
+                ("configurationDone", "{}"),
+                ("stackTrace", "{\"threadId\": 1}"),
+                ("scopes", "{\"frameId\": 1}"),
+                ("variables", "{\"variablesReference\": 1}"),
+                ("evaluate", "{\"expression\": \"n * 10\", \"frameId\": 1}"),
+                ("evaluate", "{\"expression\": \"i\", \"frameId\": 0}"),
+                ("evaluate", "{\"expression\": \"missing\"}"),
+                (
+                    "setBreakpoints",
+                    "{\"source\": {\"path\": $PATH}, \"breakpoints\": []}",
+                ),
+                ("continue", "{\"threadId\": 1}"),
+                ("disconnect", "{}"),
+            ],
+        );
+        assert_eq!(code, 0);
+        assert_eq!(
+            outline(&messages),
+            [
+                "response initialize",
+                "event initialized",
+                "response launch",
+                "response setBreakpoints",
+                "response configurationDone",
+                "event stopped",
+                "response stackTrace",
+                "response scopes",
+                "response variables",
+                "response evaluate",
+                "response evaluate",
+                "response evaluate",
+                "response setBreakpoints",
+                "response continue",
+                "event output",
+                "event exited",
+                "event terminated",
+                "response disconnect",
+            ]
+        );
+        let stopped = body(&messages, 5);
+        assert!(path(&stopped, &["reason"]).equals(&string("breakpoint")));
+
+        let trace = body(&messages, 6);
+        assert!(path(&trace, &["stackFrames", "0", "name"]).equals(&string("square")));
+        assert!(path(&trace, &["stackFrames", "0", "line"]).equals(&Types::Int(2)));
+        assert!(path(&trace, &["stackFrames", "1", "name"]).equals(&string("<script>")));
+        assert!(path(&trace, &["stackFrames", "1", "line"]).equals(&Types::Int(7)));
+
+        let scopes = body(&messages, 7);
+        assert!(path(&scopes, &["scopes", "0", "name"]).equals(&string("Locals")));
+        assert!(path(&scopes, &["scopes", "1", "name"]).equals(&string("Globals")));
+        let variables = body(&messages, 8);
+        assert!(path(&variables, &["variables", "0", "name"]).equals(&string("n")));
+        assert!(path(&variables, &["variables", "0", "value"]).equals(&string("1")));
+
+        assert!(path(&body(&messages, 9), &["result"]).equals(&string("10")));
+        assert!(path(&body(&messages, 10), &["result"]).equals(&string("1")));
+        let failed = &messages[11];
+        assert!(path(failed, &["success"]).equals(&Types::Boolean(false)));
+        assert!(path(failed, &["message"]).equals(&string("Undefined variable 'missing'.")));
+
+        assert!(path(&body(&messages, 14), &["output"]).equals(&string("5\n")));
+        assert!(path(&body(&messages, 15), &["exitCode"]).equals(&Types::Int(0)));
+    }
+
+    #[test]
+    fn test_stepping_reports_each_stop() {
+        let (_, messages) = session(
+            "stepping",
+            &[
+                ("launch", "{\"program\": $PATH, \"stopOnEntry\": true}"),
+                ("configurationDone", "{}"),
+                ("next", "{\"threadId\": 1}"),
+                ("next", "{\"threadId\": 1}"),
+                ("stepIn", "{\"threadId\": 1}"),
+                ("stepIn", "{\"threadId\": 1}"),
+                ("stackTrace", "{\"threadId\": 1}"),
+                ("stepOut", "{\"threadId\": 1}"),
+                ("stackTrace", "{\"threadId\": 1}"),
+                ("disconnect", "{}"),
+            ],
+        );
+        let stops: Vec<String> = messages
+            .iter()
+            .filter(|message| text_field(message, "event").as_deref() == Some("stopped"))
+            .map(|message| text_field(&field(message, "body").unwrap(), "reason").unwrap())
+            .collect();
+        assert_eq!(stops, ["entry", "step", "step", "step", "step", "step"]);
+
+        let lines: Vec<Types> = messages
+            .iter()
+            .filter(|message| text_field(message, "command").as_deref() == Some("stackTrace"))
+            .map(|message| path(message, &["body", "stackFrames", "0", "line"]))
+            .collect();
+        // inside `square` after stepping in, back in the loop's next iteration after stepping out
+        assert!(lines[0].equals(&Types::Int(2)));
+        assert!(lines[1].equals(&Types::Int(7)));
+        // disconnecting while stopped ends the script
+        let outline = outline(&messages);
+        assert_eq!(
+            outline[outline.len() - 3..],
+            ["response disconnect", "event exited", "event terminated"]
+        );
+    }
+
+    #[test]
+    fn test_evaluate_ignores_resolved_names_at_the_same_position() {
+        // the last `x` evaluated sits on line 1, column 21, like the one inside `f`
+        let source = "fun f(x) { { return x; } }\nvar x = 7;\nprint x;\n";
+        let (_, messages) = session_with(
+            "positions",
+            source,
+            &[
+                ("launch", "{\"program\": $PATH, \"stopOnEntry\": true}"),
+                ("configurationDone", "{}"),
+                ("next", "{\"threadId\": 1}"),
+                ("next", "{\"threadId\": 1}"),
+                ("evaluate", "{\"expression\": \"x + x + x + x + x + x\"}"),
+                ("disconnect", "{}"),
+            ],
+        );
+        let evaluated = messages
+            .iter()
+            .find(|message| text_field(message, "command").as_deref() == Some("evaluate"))
+            .unwrap();
+        assert!(path(evaluated, &["success"]).equals(&Types::Boolean(true)));
+        assert!(path(evaluated, &["body", "result"]).equals(&string("42")));
+    }
+}
@@ -0,0 +1,435 @@
+//Stepping through a script from the command line, `rlox debug FILE`
+use crate::ast::{Expr, Stmt};
+use crate::error::Error;
+use crate::interpreter::Interpreter;
+use crate::module;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+//what the interpreter calls into while a debugger is attached
+pub trait DebugHook {
+    //before each statement that starts on a line, an error stops the script
+    fn statement(&mut self, interpreter: &mut Interpreter, line: u32) -> Result<(), Error>;
+}
+
+const HELP: &str = "\
+break [file:]line  stop when a statement on that line is about to run
+step               run until the next line, going into calls
+next               run until the next line of this function
+finish             run until this function returns
+continue           run until the next breakpoint
+print expr         evaluate an expression in the current scope
+locals             show the variables of the current function
+backtrace          show the calls in progress
+quit               stop the script";
+
+//when to stop again after resuming, the depth is the call depth resuming started at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resume {
+    Continue,
+    Step,
+    Next(usize),
+    Finish(usize),
+}
+
+#[derive(Debug)]
+struct Breakpoint {
+    // `None` for `break 12`, which stops at that line of any file
+    file: Option<PathBuf>,
+    line: u32,
+}
+
+impl Breakpoint {
+    fn matches(&self, file: Option<&Path>, line: u32) -> bool {
+        line == self.line
+            && match (&self.file, file) {
+                (None, _) => true,
+                (Some(wanted), Some(file)) => file.ends_with(wanted),
+                (Some(_), None) => false,
+            }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    Step,
+    Breakpoint,
+}
+
+//Decides where a script stops, shared by the command line session and the DAP server
+#[derive(Debug)]
+pub struct Stepper {
+    breakpoints: Vec<Breakpoint>,
+    resume: Resume,
+    // file, line and depth of the statement seen last, a line only stops once until it is left
+    last: Option<(Option<PathBuf>, u32, usize)>,
+}
+
+impl Stepper {
+    //stops before the first statement until told otherwise
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            resume: Resume::Step,
+            last: None,
+        }
+    }
+
+    pub fn resume(&mut self, resume: Resume) {
+        self.resume = resume;
+    }
+
+    //returns how many breakpoints there are now
+    pub fn add_breakpoint(&mut self, file: Option<PathBuf>, line: u32) -> usize {
+        self.breakpoints.push(Breakpoint { file, line });
+        self.breakpoints.len()
+    }
+
+    //replaces the breakpoints of one file
+    pub fn set_breakpoints(&mut self, file: &Path, lines: &[u32]) {
+        let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        self.breakpoints.retain(|b| b.file.as_deref() != Some(file.as_path()));
+        for line in lines {
+            self.add_breakpoint(Some(file.clone()), *line);
+        }
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    //called before each statement, says why the script should stop there if it should
+    pub fn check(&mut self, interpreter: &Interpreter, line: u32) -> Option<StopReason> {
+        let file = interpreter.current_file().map(Path::to_path_buf);
+        let depth = interpreter.call_depth();
+        let here = (file, line, depth);
+        if self.last.as_ref() == Some(&here) {
+            return None;
+        }
+        let file = here.0.as_deref();
+        let requested = match self.resume {
+            Resume::Continue => false,
+            Resume::Step => true,
+            Resume::Next(start) => depth <= start,
+            Resume::Finish(start) => depth < start,
+        };
+        let reason = if self.breakpoints.iter().any(|b| b.matches(file, line)) {
+            Some(StopReason::Breakpoint)
+        } else if requested {
+            Some(StopReason::Step)
+        } else {
+            None
+        };
+        self.last = Some(here);
+        reason
+    }
+}
+
+impl Default for Stepper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//A command line debugging session, commands are read from `input` and answers written to `out`.
+//It stops before the first statement so breakpoints can be set.
+pub struct Session<R, W> {
+    input: R,
+    out: W,
+    stepper: Stepper,
+    sources: HashMap<PathBuf, Vec<String>>,
+}
+
+impl<R: BufRead, W: Write> Session<R, W> {
+    pub fn new(input: R, out: W) -> Self {
+        Self {
+            input,
+            out,
+            stepper: Stepper::new(),
+            sources: HashMap::new(),
+        }
+    }
+
+    fn source_line(&mut self, file: &Path, line: u32) -> Option<String> {
+        let lines = self.sources.entry(file.to_path_buf()).or_insert_with(|| {
+            fs::read_to_string(file)
+                .map(|source| source.lines().map(String::from).collect())
+                .unwrap_or_default()
+        });
+        lines.get(line as usize - 1).cloned()
+    }
+
+    fn show_location(&mut self, file: Option<&Path>, line: u32) {
+        match file {
+            Some(file) => {
+                let _ = writeln!(self.out, "Stopped at {}:{}", module::display_name(file), line);
+                if let Some(text) = self.source_line(file, line) {
+                    let _ = writeln!(self.out, "{:>4}  {}", line, text.trim_end());
+                }
+            }
+            None => {
+                let _ = writeln!(self.out, "Stopped at line {}", line);
+            }
+        }
+    }
+
+    //reads commands until one of them resumes the script
+    fn prompt(&mut self, interpreter: &mut Interpreter) -> Result<(), Error> {
+        loop {
+            let _ = write!(self.out, "(rlox) ");
+            let _ = self.out.flush();
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                // no one is left to type commands, let the script run to its end
+                Ok(0) | Err(_) => {
+                    let _ = writeln!(self.out);
+                    self.stepper.clear_breakpoints();
+                    self.stepper.resume(Resume::Continue);
+                    return Ok(());
+                }
+                Ok(_) => {}
+            }
+            let (command, argument) = match line.trim().split_once(' ') {
+                Some((command, argument)) => (command, argument.trim()),
+                None => (line.trim(), ""),
+            };
+            let depth = interpreter.call_depth();
+            match command {
+                "" => {}
+                "break" | "b" => self.add_breakpoint(argument),
+                "step" | "s" => return self.resume(Resume::Step),
+                "next" | "n" => return self.resume(Resume::Next(depth)),
+                "finish" if depth == 0 => {
+                    let _ = writeln!(self.out, "Not inside a function.");
+                }
+                "finish" => return self.resume(Resume::Finish(depth)),
+                "continue" | "c" => return self.resume(Resume::Continue),
+                "print" | "p" => self.print(interpreter, argument),
+                "locals" => self.locals(interpreter),
+                "backtrace" | "bt" => self.backtrace(interpreter),
+                "quit" | "q" => return Err(Error::Exit { code: 0 }),
+                "help" | "h" => {
+                    let _ = writeln!(self.out, "{}", HELP);
+                }
+                other => {
+                    let _ = writeln!(self.out, "Unknown command '{}', try 'help'.", other);
+                }
+            }
+        }
+    }
+
+    fn resume(&mut self, resume: Resume) -> Result<(), Error> {
+        self.stepper.resume(resume);
+        Ok(())
+    }
+
+    fn add_breakpoint(&mut self, argument: &str) {
+        let (file, line) = match argument.rsplit_once(':') {
+            Some((file, line)) => (Some(PathBuf::from(file)), line),
+            None => (None, argument),
+        };
+        match line.parse::<u32>() {
+            Ok(line) if line > 0 => {
+                let shown = file.as_ref().map(|file| file.display().to_string());
+                let number = self.stepper.add_breakpoint(file, line);
+                let _ = match shown {
+                    Some(file) => writeln!(self.out, "Breakpoint {} at {}:{}", number, file, line),
+                    None => writeln!(self.out, "Breakpoint {} at line {}", number, line),
+                };
+            }
+            _ => {
+                let _ = writeln!(self.out, "Usage: break [file:]line");
+            }
+        }
+    }
+
+    fn print(&mut self, interpreter: &mut Interpreter, source: &str) {
+        let expr = match parse_expression(source) {
+            Some(Ok(expr)) => expr,
+            Some(Err(_)) => {
+                let _ = writeln!(self.out, "Usage: print expr");
+                return;
+            }
+            None => return,
+        };
+        let _ = match interpreter.evaluate(&expr) {
+            Ok(value) => writeln!(self.out, "{}", interpreter.stringify(value)),
+            Err(error) => writeln!(self.out, "Error: {}", error_message(error)),
+        };
+    }
+
+    //the scopes of the current function or top level, innermost first
+    fn locals(&mut self, interpreter: &Interpreter) {
+        let mut found = false;
+        let mut env = Some(interpreter.environment());
+        while let Some(current) = env.filter(|env| !env.is_top_level()) {
+            let mut names = Vec::new();
+            current.for_each(|name, value| names.push((name.to_string(), value.clone())));
+            names.sort_by(|a, b| a.0.cmp(&b.0));
+            for (name, value) in names {
+                let _ = writeln!(self.out, "{} = {}", name, interpreter.stringify(value));
+                found = true;
+            }
+            env = current.enclosing();
+        }
+        if !found {
+            let _ = writeln!(self.out, "No locals.");
+        }
+    }
+
+    fn backtrace(&mut self, interpreter: &Interpreter) {
+        for (i, frame) in interpreter.call_stack().iter().rev().enumerate() {
+            let _ = match &frame.file {
+                Some(file) => writeln!(
+                    self.out,
+                    "#{} {} at {}:{}",
+                    i,
+                    frame.name,
+                    module::display_name(file),
+                    frame.line
+                ),
+                None => writeln!(self.out, "#{} {} at line {}", i, frame.name, frame.line),
+            };
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> DebugHook for Session<R, W> {
+    fn statement(&mut self, interpreter: &mut Interpreter, line: u32) -> Result<(), Error> {
+        if self.stepper.check(interpreter, line).is_none() {
+            return Ok(());
+        }
+        let file = interpreter.current_file().map(Path::to_path_buf);
+        self.show_location(file.as_deref(), line);
+        self.prompt(interpreter)
+    }
+}
+
+//parses a single expression typed while stopped, `None` if the scanner or parser already
+//reported an error, `Some(Err)` if it parsed to something other than one expression
+pub fn parse_expression(source: &str) -> Option<Result<Expr, ()>> {
+    let source = format!("{};", source);
+    let mut scanner = Scanner::new(source.as_bytes());
+    let tokens = scanner.scan_tokens();
+    let mut parser = Parser::new(tokens);
+    match parser.parse() {
+        Ok(mut statements) if !scanner.had_error() && !parser.had_error() => {
+            match (statements.pop(), statements.is_empty()) {
+                (Some(Stmt::Expr(expr)), true) => Some(Ok(expr)),
+                _ => Some(Err(())),
+            }
+        }
+        _ => None,
+    }
+}
+
+//what to show for an error raised while evaluating an expression for the user
+pub fn error_message(error: Error) -> String {
+    match error {
+        Error::RunTime { message, .. } => message,
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::CapturedOutput;
+    use crate::resolver::Resolver;
+    use std::io::Cursor;
+
+    const SCRIPT: &str = "fun square(n) {
+    var result = n * n;
+    return result;
+}
+var total = 0;
+for (var i = 1; i <= 2; i = i + 1) {
+    total = total + square(i);
+}
+print total;
+";
+
+    //runs SCRIPT from a file under a session fed `commands`, returns its transcript
+    fn debug(name: &str, commands: &str) -> (Result<(), Error>, String) {
+        debug_script(name, SCRIPT, commands)
+    }
+
+    fn debug_script(name: &str, script: &str, commands: &str) -> (Result<(), Error>, String) {
+        let path = std::env::temp_dir().join(format!("rlox-debug-{}-{}.lox", name, std::process::id()));
+        fs::write(&path, script).unwrap();
+        let transcript = CapturedOutput::default();
+        let mut interpreter = Interpreter::with_output(Box::new(CapturedOutput::default()));
+        interpreter.set_script_path(&path);
+        let session = Session::new(Cursor::new(commands.to_string()), transcript.clone());
+        interpreter.set_debugger(Box::new(session));
+
+        let statements = Parser::new(Scanner::new(script.as_bytes()).scan_tokens())
+            .parse()
+            .unwrap();
+        Resolver::new(&mut interpreter).resolve_stmts(&statements);
+        let result = interpreter.interpret(statements);
+        fs::remove_file(&path).unwrap();
+        let file = module::display_name(&path);
+        (result, transcript.contents().replace(&file, "main.lox"))
+    }
+
+    #[test]
+    fn test_breakpoint_inspects_the_paused_call() {
+        let (result, transcript) = debug(
+            "inspect",
+            "break 2\ncontinue\nlocals\nprint n * 10\nprint missing\nbacktrace\ncontinue\ncontinue\n",
+        );
+        assert!(result.is_ok());
+        let expected = "Stopped at main.lox:1
+   1  fun square(n) {
+(rlox) Breakpoint 1 at line 2
+(rlox) Stopped at main.lox:2
+   2      var result = n * n;
+(rlox) n = 1
+(rlox) 10
+(rlox) Error: Undefined variable 'missing'.
+(rlox) #0 square at main.lox:2
+#1 <script> at main.lox:7
+(rlox) Stopped at main.lox:2
+   2      var result = n * n;
+(rlox) ";
+        assert_eq!(transcript, expected);
+    }
+
+    #[test]
+    fn test_next_steps_over_calls_and_finish_leaves_them() {
+        let (_, transcript) = debug("stepping", "next\nnext\nnext\nstep\nstep\nfinish\nquit\n");
+        let stops: Vec<&str> = transcript
+            .lines()
+            .filter_map(|line| line.strip_prefix("(rlox) Stopped at "))
+            .collect();
+        // the for loop, the call in its body, into `square`, then back out to the next iteration
+        assert_eq!(
+            stops,
+            ["main.lox:5", "main.lox:6", "main.lox:7", "main.lox:2", "main.lox:3", "main.lox:7"]
+        );
+    }
+
+    #[test]
+    fn test_print_ignores_resolved_names_at_the_same_position() {
+        // the last `x` typed sits on line 1, column 21, like the one inside `f`
+        let script = "fun f(x) { { return x; } }\nvar x = 7;\nprint x;\n";
+        let (result, transcript) = debug_script(
+            "positions",
+            script,
+            "next\nnext\nprint x + x + x + x + x + x\ncontinue\n",
+        );
+        assert!(result.is_ok());
+        assert!(transcript.contains("(rlox) 42\n"), "{}", transcript);
+    }
+
+    #[test]
+    fn test_quit_stops_the_script() {
+        let (result, _) = debug("quit", "quit\n");
+        assert!(std::matches!(result, Err(Error::Exit { code: 0 })));
+    }
+}
@@ -1,109 +1,245 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::f64::consts::E;
-use std::rc::Rc;
-use std::string;
+use std::rc::{Rc, Weak};
 use crate::error::Error;
 use crate::error::Error::RunTime;
+use crate::heap;
 use crate::interpreter::Types;
-use crate::token::{Token, TokenType};
-
-
+use crate::token::Token;
 
+//An environment is a shared handle to one scope of variables. Cloning it, or capturing it in a
+//closure, refers to the very same scope, so an update made through one handle is seen by all.
 #[derive(Clone, Debug)]
-pub struct Environment{
-    pub enclosing: Option<Box<Environment>>,
-    values: Rc<RefCell<HashMap<std::string::String, Types>>>
+pub struct Environment(Rc<Scope>);
+
+#[derive(Debug)]
+pub struct Scope {
+    enclosing: Option<Environment>,
+    // the globals and each module's top level, where names the resolver left alone are found
+    top_level: bool,
+    values: RefCell<HashMap<String, Types>>,
 }
 
 impl Environment{
     pub fn new() -> Self {
-        Self{
-            enclosing: None,
-            values: Rc::new(RefCell::new(HashMap::new()))
-        }
+        Self::with_scope(None, true)
     }
 
     pub fn from(enclosing: Environment) -> Self {
-        Self {
-            enclosing: Some(Box::new(enclosing)),
-            values: Rc::new(RefCell::new(HashMap::new()))
+        Self::with_scope(Some(enclosing), false)
+    }
+
+    //the top level of an imported module, it sees the globals but keeps its own definitions
+    pub fn module(globals: Environment) -> Self {
+        Self::with_scope(Some(globals), true)
+    }
+
+    fn with_scope(enclosing: Option<Environment>, top_level: bool) -> Self {
+        let env = Self(Rc::new(Scope {
+            enclosing,
+            top_level,
+            values: RefCell::new(HashMap::new()),
+        }));
+        heap::track(&env);
+        env
+    }
+
+    pub fn enclosing(&self) -> Option<&Environment> {
+        self.0.enclosing.as_ref()
+    }
+
+    pub fn is_top_level(&self) -> bool {
+        self.0.top_level
+    }
+
+    pub fn define(&self, name: String, value: Option<Types>){
+        self.0.values.borrow_mut().insert(name, value.unwrap_or(Types::Nil));
+    }
+
+    //a value defined directly in this environment, enclosing ones are not searched
+    pub fn get_own(&self, name: &str) -> Option<Types> {
+        self.0.values.borrow().get(name).cloned()
+    }
+
+    //identifies the scope, clones of an environment share it
+    pub fn id(&self) -> *const () {
+        Rc::as_ptr(&self.0) as *const ()
+    }
+
+    //handles to this scope, including the ones held by closures and inner scopes
+    pub fn strong_count(&self) -> usize {
+        Rc::strong_count(&self.0)
+    }
+
+    pub fn downgrade(&self) -> Weak<Scope> {
+        Rc::downgrade(&self.0)
+    }
+
+    pub fn upgrade(scope: &Weak<Scope>) -> Option<Environment> {
+        scope.upgrade().map(Environment)
+    }
+
+    //visits the variables defined directly in this environment
+    pub fn for_each(&self, mut f: impl FnMut(&str, &Types)) {
+        for (name, value) in self.0.values.borrow().iter() {
+            f(name, value);
         }
     }
 
-    pub fn define(&self, name: std::string::String, value: Option<Types>){
-        self.values.borrow_mut().insert(name, value.unwrap());
+    //like `for_each`, but gives up and returns false while the variables are being changed
+    pub fn try_for_each(&self, mut f: impl FnMut(&str, &Types)) -> bool {
+        match self.0.values.try_borrow() {
+            Ok(values) => {
+                for (name, value) in values.iter() {
+                    f(name, value);
+                }
+                true
+            }
+            Err(_) => false,
+        }
     }
 
-    fn ancestor(&self, distance: &usize) -> Box<Environment> {
-        let parent = self.enclosing.clone().expect(&format!("No enclosing environment at {}", 1));
-        let mut env = Box::clone(&parent);
+    //drops every variable, the collector uses it to break cycles through unreachable scopes
+    pub fn clear(&self) {
+        let values = std::mem::take(&mut *self.0.values.borrow_mut());
+        // dropped after the borrow ends, freeing the values may clear other scopes in turn
+        drop(values);
+    }
 
-        for i in 1..*distance {
-            let parent = self.enclosing.clone().expect(&format!("No enclosing environment at {}", i));
-            env = Box::clone(&parent);
+    fn ancestor(&self, distance: &usize, name: &Token) -> Result<&Environment, Error> {
+        let mut env = self;
+        for i in 0..*distance {
+            env = env.enclosing().ok_or_else(|| RunTime {
+                token: name.clone(),
+                message: format!("No enclosing environment at {} for '{}'.", i + 1, name.lexeme),
+            })?;
         }
-        env
+        Ok(env)
     }
 
-    pub fn get_at(&self, distance: &usize, name: &Token) -> Result<Types, Error> {
-        let key = &*name.lexeme;
-        let key_str = string::String::from(key);
-        if distance > &0 {
-        Ok(self.ancestor(distance).values.borrow().get(&key_str).expect(&format!("Undefined variable '{}'", key)).clone())
-        }else{
-         Ok(self.values.borrow().get(&key_str).expect(&format!("Undefined variable '{}'", key)).clone())
+    fn undefined(name: &Token) -> Error {
+        RunTime {
+            token: name.clone(),
+            message: format!("Undefined variable '{}'.", name.lexeme),
         }
     }
 
-    pub fn get(&mut self, name: &Token) -> Result<Types, Error> {
-        let name_lexeme = name.lexeme.clone();
-        if self.values.borrow().contains_key(&name_lexeme) == true{
-            // println!("{:?}",self.values.clone().borrow().get(&name).cloned().unwrap());
-            Ok(self.values.borrow_mut().get(&name_lexeme).unwrap().clone())
-        }else {
-            if let Some(ref mut enclosing) = self.enclosing {
-               enclosing.get(name)
-            }
-            else {
-                Err(RunTime {
-                    token: Token {
-                        t_type: TokenType::Identifier(name_lexeme.clone()),
-                        lexeme: name_lexeme,
-                        line: 0,
-                    },
-                    message: "cannot get the value or hasn't been assigned yet".to_string(),
-                })
-            }
+    //a variable the resolver found `distance` scopes out
+    pub fn get_at(&self, distance: &usize, name: &Token) -> Result<Types, Error> {
+        self.ancestor(distance, name)?
+            .get_own(&name.lexeme)
+            .ok_or_else(|| Self::undefined(name))
+    }
 
+    //searches this environment and every enclosing one
+    pub fn get(&self, name: &Token) -> Result<Types, Error> {
+        let mut env = Some(self);
+        while let Some(current) = env {
+            if let Some(value) = current.get_own(&name.lexeme) {
+                return Ok(value);
+            }
+            env = current.enclosing();
         }
+        Err(Self::undefined(name))
     }
 
-    pub fn assign_at(&mut self, name: &Token, value: &Types, distance: &usize) -> Result <(), Error> {
-        let key = &*name.lexeme;
-        let key_str = string::String::from(key);
-        if distance > &0{
-            self.ancestor(distance).values.borrow_mut().insert(key_str, value.clone());
-        }else {
-            self.values.borrow_mut().insert(key_str, value.clone());
+    //a name the resolver did not find in any scope, it is a module's or a global one.
+    //Blocks in between are skipped, a variable they declare later must not shadow it.
+    pub fn get_top_level(&self, name: &Token) -> Result<Types, Error> {
+        let mut env = Some(self);
+        while let Some(current) = env {
+            if current.0.top_level {
+                if let Some(value) = current.get_own(&name.lexeme) {
+                    return Ok(value);
+                }
+            }
+            env = current.enclosing();
         }
+        Err(Self::undefined(name))
+    }
+
+    pub fn assign_at(&self, name: &Token, value: &Types, distance: &usize) -> Result <(), Error> {
+        self.ancestor(distance, name)?
+            .0
+            .values
+            .borrow_mut()
+            .insert(name.lexeme.clone(), value.clone());
         Ok(())
     }
 
-    pub fn assign(&mut self, name: &Token, value: &Types) -> Result<(), Error> {
-        let ident_name = name.lexeme.clone();
-        if self.values.borrow().contains_key(&ident_name)  == true {
-            self.values.borrow_mut().insert(ident_name.clone(), value.clone());
-            Ok(())
-        }else {
-            if let Some(ref mut enclosing)= self.enclosing{
-                enclosing.assign(name, value)
-            }else {
-                Err(RunTime {
-                    token: name.clone(),
-                    message: "cannot get the value or hasn't been assigned yet".to_string(),
-                })
+    pub fn assign(&self, name: &Token, value: &Types) -> Result<(), Error> {
+        self.assign_where(name, value, |_| true)
+    }
+
+    //assigns a name the resolver did not find in any scope, see `get_top_level`
+    pub fn assign_top_level(&self, name: &Token, value: &Types) -> Result<(), Error> {
+        self.assign_where(name, value, |env| env.0.top_level)
+    }
+
+    //assigns in the innermost environment `searched` accepts that defines the name
+    fn assign_where(
+        &self,
+        name: &Token,
+        value: &Types,
+        searched: impl Fn(&Environment) -> bool,
+    ) -> Result<(), Error> {
+        let mut env = Some(self);
+        while let Some(current) = env {
+            if searched(current) && current.0.values.borrow().contains_key(&name.lexeme) {
+                current.0.values.borrow_mut().insert(name.lexeme.clone(), value.clone());
+                return Ok(());
             }
+            env = current.enclosing();
         }
+        Err(Self::undefined(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::TokenType;
+
+    fn name(lexeme: &str) -> Token {
+        Token {
+            t_type: TokenType::Identifier(lexeme.to_string()),
+            lexeme: lexeme.to_string(),
+            line: 1,
+            column: 0,
+        }
+    }
+
+    #[test]
+    fn test_get_at_reaches_every_distance() {
+        let globals = Environment::new();
+        globals.define("a".to_string(), Some(Types::Int(0)));
+        let middle = Environment::from(globals.clone());
+        middle.define("a".to_string(), Some(Types::Int(1)));
+        let inner = Environment::from(Environment::from(middle.clone()));
+
+        assert!(inner.get_at(&2, &name("a")).unwrap().equals(&Types::Int(1)));
+        assert!(inner.get_at(&3, &name("a")).unwrap().equals(&Types::Int(0)));
+        inner.assign_at(&name("a"), &Types::Int(5), &3).unwrap();
+        assert!(globals.get_own("a").unwrap().equals(&Types::Int(5)));
+    }
+
+    #[test]
+    fn test_clones_share_their_variables() {
+        let env = Environment::from(Environment::new());
+        let captured = env.clone();
+        env.define("n".to_string(), Some(Types::Int(1)));
+        captured.assign(&name("n"), &Types::Int(2)).unwrap();
+        assert!(env.get(&name("n")).unwrap().equals(&Types::Int(2)));
+        assert_eq!(env.id(), captured.id());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_top_level_lookup_skips_blocks() {
+        let globals = Environment::new();
+        globals.define("a".to_string(), Some(Types::Int(0)));
+        let block = Environment::from(globals.clone());
+        block.define("a".to_string(), Some(Types::Int(1)));
+        assert!(block.get_top_level(&name("a")).unwrap().equals(&Types::Int(0)));
+        assert!(block.get(&name("a")).unwrap().equals(&Types::Int(1)));
+    }
+}
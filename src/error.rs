@@ -1,24 +1,70 @@
+use std::cell::RefCell;
 use std::fmt;
 use std::fmt::{Debug, Formatter, write};
 use crate::interpreter::Types;
 use crate::token::{Token, TokenType};
 
-pub fn error(line: u32, message:&str)  {
-    report_err(line, "", message)
+//a compile error as tools see it, `length` is how many characters from `column` it covers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: u32,
+    pub column: u32,
+    pub length: usize,
+    pub message: String,
 }
 
-pub fn report_err(line: u32, where_: &str, message: &str) {
-    eprintln!("[line {}] Error{}: {}", line, where_, message);
+thread_local! {
+    // set while `capture` runs, compile errors are kept here instead of printed
+    static CAPTURED: RefCell<Option<Vec<Diagnostic>>> = const { RefCell::new(None) };
+}
+
+//runs `f` with compile errors collected rather than printed to stderr
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<Diagnostic>) {
+    let previous = CAPTURED.with(|captured| captured.replace(Some(Vec::new())));
+    let result = f();
+    let diagnostics = CAPTURED.with(|captured| captured.replace(previous));
+    (result, diagnostics.unwrap_or_default())
+}
+
+pub fn error(line: u32, column: u32, message:&str)  {
+    report(Diagnostic { line, column, length: 1, message: message.to_string() }, "")
 }
 
 pub fn parse_error(token: &Token, message:&str) {
+    let diagnostic = Diagnostic {
+        line: token.line,
+        column: token.column,
+        length: token.lexeme.chars().count().max(1),
+        message: message.to_string(),
+    };
     if token.t_type == TokenType::EOF {
-        report_err(token.line, "at end", message);
+        report(diagnostic, " at end");
     }else {
-        report_err(token.line, &format!(" at '{}'", token.lexeme), message);
+        report(diagnostic, &format!(" at '{}'", token.lexeme));
     }
 }
 
+fn report(diagnostic: Diagnostic, where_: &str) {
+    let diagnostic = CAPTURED.with(|captured| match captured.borrow_mut().as_mut() {
+        Some(diagnostics) => {
+            diagnostics.push(diagnostic);
+            None
+        }
+        None => Some(diagnostic),
+    });
+    if let Some(diagnostic) = diagnostic {
+        report_err(diagnostic.line, where_, &diagnostic.message);
+    }
+}
+
+pub fn report_err(line: u32, where_: &str, message: &str) {
+    eprintln!("[line {}] Error{}: {}", line, where_, message);
+}
+
+pub fn runtime_error(token: &Token, message: &str) {
+    eprintln!("{}\n[line {}]", message, token.line);
+}
+
 #[derive(Debug)]
 pub enum Error {
     Parse,
@@ -29,15 +75,52 @@ pub enum Error {
     Return {
         value: Types
     },
+    // `throw value;` unwinding to the nearest catch, the token is the throw keyword
+    Throw {
+        token: Token,
+        value: Types,
+    },
+    // `exit(code)` unwinding all the way out of the interpreter, catch blocks let it pass
+    Exit {
+        code: i32,
+    },
+    // a resource limit of the interpreter ran out, the script is stopped and cannot catch it
+    Limit {
+        message: String,
+    },
     InvalidStmt
 }
 
+//what a catch block sees for a runtime error raised by the interpreter or a native
+#[derive(Debug)]
+pub struct ErrorValue {
+    pub message: String,
+    pub line: u32,
+}
+
+impl Error {
+    //the value a `catch (e)` binds, returns and parse errors are not caught
+    pub fn caught_value(self) -> Result<Types, Error> {
+        match self {
+            Error::RunTime { token, message } => Ok(Types::Error(std::rc::Rc::new(ErrorValue {
+                message,
+                line: token.line,
+            }))),
+            Error::Throw { value, .. } => Ok(value),
+            other => Err(other),
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
      match self {
          Error::Parse => write!(f, "ParseError"),
          Error::RunTime {message, ..} => write!(f, "RuntimeError {}", message),
          Error::Return {value}=> write!(f,"Return {:?}", value ),
+         Error::Throw {value, ..} => write!(f, "Uncaught exception: {}", value),
+         Error::Exit {code} => write!(f, "exit({})", code),
+         Error::Limit {message} => write!(f, "{}", message),
          Error::InvalidStmt => write!(f,"invalid Statement or Null")
      }
     }
@@ -0,0 +1,577 @@
+use crate::ast::{Expr, Stmt, Visitor};
+use crate::error::Error;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::token::{Comment, Token, TokenType};
+use std::collections::VecDeque;
+
+const INDENT: &str = "    ";
+
+//Formatter regenerates canonical Lox source from the AST
+//comments kept by the scanner are put back in front of the statement that follows or contains them
+pub struct Formatter {
+    out: String,
+    indent: usize,
+    at_line_start: bool,
+    comments: VecDeque<Comment>,
+    // ids of the currently open braces, same numbering the scanner uses
+    blocks: Vec<usize>,
+    block_count: usize,
+    last_line: Option<u32>,
+}
+
+//scans, parses and formats a whole source file
+pub fn format_source(source: &str) -> Result<String, Error> {
+    let mut scanner = Scanner::new(source.as_bytes());
+    let tokens = scanner.scan_tokens();
+    if scanner.had_error() {
+        return Err(Error::Parse);
+    }
+    let comments = scanner.take_comments();
+    let statements = Parser::new(tokens).parse()?;
+
+    let mut formatter = Formatter::new(comments);
+    formatter.format(&statements)?;
+    Ok(formatter.out)
+}
+
+impl Formatter {
+    pub fn new(comments: Vec<Comment>) -> Self {
+        Self {
+            out: String::new(),
+            indent: 0,
+            at_line_start: true,
+            comments: comments.into(),
+            blocks: vec![0],
+            block_count: 0,
+            last_line: None,
+        }
+    }
+
+    pub fn format(&mut self, statements: &[Stmt]) -> Result<(), Error> {
+        self.statements(statements, false)?;
+        // whatever is left belongs after the last statement
+        while let Some(comment) = self.comments.pop_front() {
+            self.comment(&comment);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.at_line_start {
+            for _ in 0..self.indent {
+                self.out.push_str(INDENT);
+            }
+            self.at_line_start = false;
+        }
+        self.out.push_str(text);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.at_line_start = true;
+    }
+
+    fn comment(&mut self, comment: &Comment) {
+        self.blank_line_before(comment.line);
+        self.write(&comment.text);
+        self.newline();
+        self.last_line = Some(comment.line + comment.text.matches('\n').count() as u32);
+    }
+
+    // keeps a single empty line where the source had one or more
+    fn blank_line_before(&mut self, line: u32) {
+        if let Some(last) = self.last_line {
+            if line > last + 1 {
+                self.newline();
+            }
+        }
+    }
+
+    fn current_block(&self) -> usize {
+        *self.blocks.last().unwrap_or(&0)
+    }
+
+    // writes out the comments of the current block that come before `line`
+    fn leading_comments(&mut self, line: u32) {
+        while let Some(comment) = self.comments.front() {
+            if comment.block != self.current_block() || comment.line >= line {
+                break;
+            }
+            let comment = self.comments.pop_front().unwrap();
+            self.comment(&comment);
+        }
+    }
+
+    fn statements(&mut self, statements: &[Stmt], methods: bool) -> Result<(), Error> {
+        for statement in statements {
+            let (start, end) = lines(statement).unwrap_or((0, 0));
+            // a comment inside a statement spanning lines goes above it, the code is rejoined
+            self.leading_comments(end);
+            self.blank_line_before(start);
+
+            match statement {
+                Stmt::Function(name, params, body, _) if methods => {
+                    self.function(name, params, body)?
+                }
+                _ => self.visit_statement(statement)?,
+            }
+
+            if !ends_with_brace(statement) {
+                if let Some(comment) = self.comments.front() {
+                    if comment.block == self.current_block() && comment.line == end {
+                        let comment = self.comments.pop_front().unwrap();
+                        self.write(&format!(" {}", comment.text));
+                    }
+                }
+            }
+            self.newline();
+            // the closing brace sits below whatever was last written inside it
+            self.last_line = Some(if ends_with_brace(statement) {
+                end.max(self.last_line.unwrap_or(end)) + 1
+            } else {
+                end
+            });
+        }
+        Ok(())
+    }
+
+    // opens a brace, runs `body` inside it and closes it again
+    fn braced<F>(&mut self, body: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.block_count += 1;
+        let id = self.block_count;
+        self.blocks.push(id);
+        self.write("{");
+        self.newline();
+        self.indent += 1;
+        self.last_line = None;
+
+        body(self)?;
+        while let Some(comment) = self.comments.front() {
+            if comment.block != id {
+                break;
+            }
+            let comment = self.comments.pop_front().unwrap();
+            self.comment(&comment);
+        }
+
+        self.indent -= 1;
+        self.blocks.pop();
+        self.write("}");
+        Ok(())
+    }
+
+    fn block(&mut self, statements: &[Stmt]) -> Result<(), Error> {
+        self.braced(|f| f.statements(statements, false))
+    }
+
+    // the body of an if, while or for: blocks keep their braces, anything else stays inline
+    fn body(&mut self, statement: &Stmt) -> Result<(), Error> {
+        self.write(" ");
+        self.visit_statement(statement)
+    }
+
+    fn function(&mut self, name: &Token, params: &[Token], body: &[Stmt]) -> Result<(), Error> {
+        let params = params
+            .iter()
+            .map(|p| p.lexeme.clone())
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.write(&format!("{}({}) ", name.lexeme, params));
+        self.block(body)
+    }
+
+    // statements that fit on a single line, also used inside a for clause
+    fn simple_statement(&mut self, statement: &Stmt) -> Result<String, Error> {
+        Ok(match statement {
+            Stmt::Expr(expr) => format!("{};", self.visit_expression(expr)),
+            Stmt::Print(expr) => format!("print {};", self.visit_expression(expr)),
+            Stmt::Return(_, None) => "return;".to_string(),
+            Stmt::Return(_, Some(expr)) => format!("return {};", self.visit_expression(expr)),
+            Stmt::Throw(_, value) => format!("throw {};", self.visit_expression(value)),
+            Stmt::Import(path, None, names) if names.is_empty() => format!("import {};", literal(path)),
+            Stmt::Import(path, Some(alias), _) => {
+                format!("import {} as {};", literal(path), alias.lexeme)
+            }
+            Stmt::Import(path, None, names) => {
+                // the scanner numbered the braces around the names like a block's
+                self.block_count += 1;
+                format!(
+                    "import {{ {} }} from {};",
+                    names.iter().map(|name| name.lexeme.as_str()).collect::<Vec<_>>().join(", "),
+                    literal(path)
+                )
+            }
+            Stmt::VarDeclaration(name, None) => format!("var {};", name.lexeme),
+            Stmt::VarDeclaration(name, Some(expr)) => {
+                format!("var {} = {};", name.lexeme, self.visit_expression(expr))
+            }
+            _ => return Err(Error::InvalidStmt),
+        })
+    }
+}
+
+impl Visitor for Formatter {
+    type E = String;
+    type S = Result<(), Error>;
+
+    fn visit_expression(&mut self, e: &Expr) -> Self::E {
+        match e {
+            Expr::Assign { name, value } => {
+                format!("{} = {}", name.lexeme, self.visit_expression(value))
+            }
+            Expr::CompoundAssign { name, op, value } => format!(
+                "{} {} {}",
+                name.lexeme,
+                op.t_type,
+                self.visit_expression(value)
+            ),
+            Expr::Binary { left, op, right } | Expr::Logical { left, op, right } => format!(
+                "{} {} {}",
+                self.visit_expression(left),
+                op.t_type,
+                self.visit_expression(right)
+            ),
+            Expr::Call {
+                callee, arguments, ..
+            } => format!(
+                "{}({})",
+                self.visit_expression(callee),
+                arguments
+                    .iter()
+                    .map(|a| self.visit_expression(a))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Expr::Get {
+                object,
+                name,
+                optional,
+            } => format!(
+                "{}{}{}",
+                self.visit_expression(object),
+                if *optional { "?." } else { "." },
+                name.lexeme
+            ),
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => format!(
+                "{} ? {} : {}",
+                self.visit_expression(condition),
+                self.visit_expression(then_branch),
+                self.visit_expression(else_branch)
+            ),
+            Expr::Grouping { expr } => format!("({})", self.visit_expression(expr)),
+            Expr::OptionalChain { expr } => self.visit_expression(expr),
+            Expr::Literal { token } => literal(token),
+            Expr::Unary { op, expr } => format!("{}{}", op.t_type, self.visit_expression(expr)),
+            Expr::Variable { name } => name.lexeme.clone(),
+        }
+    }
+
+    fn visit_statement(&mut self, s: &Stmt) -> Self::S {
+        match s {
+            Stmt::Block(statements) => self.block(statements),
+            Stmt::Class(name, methods, _) => {
+                self.write(&format!("class {} ", name.lexeme));
+                self.braced(|f| f.statements(methods, true))
+            }
+            Stmt::For(initializer, condition, increment, body) => {
+                let mut head = String::from("for (");
+                match initializer {
+                    Some(init) => head.push_str(&self.simple_statement(init)?),
+                    None => head.push(';'),
+                }
+                if let Some(cond) = condition {
+                    head.push(' ');
+                    head.push_str(&self.visit_expression(cond));
+                }
+                head.push(';');
+                if let Some(inc) = increment {
+                    head.push(' ');
+                    head.push_str(&self.visit_expression(inc));
+                }
+                head.push(')');
+                self.write(&head);
+                self.body(body)
+            }
+            Stmt::Function(name, params, body, _) => {
+                self.write("fun ");
+                self.function(name, params, body)
+            }
+            Stmt::IfStmt(condition, then, else_branch) => {
+                let condition = self.visit_expression(condition);
+                self.write(&format!("if ({})", condition));
+                self.body(then)?;
+                if let Some(else_branch) = else_branch {
+                    self.write(" else");
+                    self.body(else_branch)?;
+                }
+                Ok(())
+            }
+            Stmt::Test(name, body) => {
+                self.write(&format!("test {} ", literal(name)));
+                self.block(body)
+            }
+            Stmt::Try(_, body, catch, finally) => {
+                self.write("try ");
+                self.block(body)?;
+                if let Some((name, handler)) = catch {
+                    self.write(&format!(" catch ({}) ", name.lexeme));
+                    self.block(handler)?;
+                }
+                if let Some(finally) = finally {
+                    self.write(" finally ");
+                    self.block(finally)?;
+                }
+                Ok(())
+            }
+            Stmt::While(condition, body) => {
+                let condition = self.visit_expression(condition);
+                self.write(&format!("while ({})", condition));
+                self.body(body)
+            }
+            Stmt::Expr(_)
+            | Stmt::Import(..)
+            | Stmt::Print(_)
+            | Stmt::Return(..)
+            | Stmt::Throw(..)
+            | Stmt::VarDeclaration(..) => {
+                let line = self.simple_statement(s)?;
+                self.write(&line);
+                Ok(())
+            }
+            // a statement that failed to parse, formatting would lose it
+            Stmt::Null => Err(Error::Parse),
+        }
+    }
+}
+
+fn literal(token: &Token) -> String {
+    if token.lexeme.is_empty() {
+        match &token.t_type {
+            TokenType::String(s) => format!("\"{}\"", s),
+            other => other.to_string(),
+        }
+    } else {
+        token.lexeme.clone()
+    }
+}
+
+fn ends_with_brace(statement: &Stmt) -> bool {
+    match statement {
+        Stmt::Block(_)
+        | Stmt::Class(..)
+        | Stmt::Function(..)
+        | Stmt::Test(..)
+        | Stmt::Try(..) => true,
+        Stmt::For(_, _, _, body) | Stmt::While(_, body) => ends_with_brace(body),
+        Stmt::IfStmt(_, then, else_branch) => match else_branch {
+            Some(else_branch) => ends_with_brace(else_branch),
+            None => ends_with_brace(then),
+        },
+        _ => false,
+    }
+}
+
+//first and last line of the tokens inside a statement
+fn lines(statement: &Stmt) -> Option<(u32, u32)> {
+    let mut tokens = Vec::new();
+    stmt_tokens(statement, &mut tokens);
+    let min = tokens.iter().map(|t| t.line).min()?;
+    let max = tokens.iter().map(|t| t.line).max()?;
+    Some((min, max))
+}
+
+fn stmt_tokens<'a>(statement: &'a Stmt, tokens: &mut Vec<&'a Token>) {
+    match statement {
+        Stmt::Block(statements) => statements.iter().for_each(|s| stmt_tokens(s, tokens)),
+        Stmt::Class(name, methods, _) => {
+            tokens.push(name);
+            methods.iter().for_each(|s| stmt_tokens(s, tokens));
+        }
+        Stmt::Expr(expr) | Stmt::Print(expr) => expr_tokens(expr, tokens),
+        Stmt::For(initializer, condition, increment, body) => {
+            if let Some(init) = initializer {
+                stmt_tokens(init, tokens);
+            }
+            condition.iter().chain(increment).for_each(|e| expr_tokens(e, tokens));
+            stmt_tokens(body, tokens);
+        }
+        Stmt::Function(name, params, body, _) => {
+            tokens.push(name);
+            tokens.extend(params);
+            body.iter().for_each(|s| stmt_tokens(s, tokens));
+        }
+        Stmt::Import(path, alias, names) => {
+            tokens.push(path);
+            tokens.extend(alias);
+            tokens.extend(names);
+        }
+        Stmt::IfStmt(condition, then, else_branch) => {
+            expr_tokens(condition, tokens);
+            stmt_tokens(then, tokens);
+            if let Some(else_branch) = else_branch {
+                stmt_tokens(else_branch, tokens);
+            }
+        }
+        Stmt::Return(keyword, value) => {
+            tokens.push(keyword);
+            value.iter().for_each(|e| expr_tokens(e, tokens));
+        }
+        Stmt::Test(name, body) => {
+            tokens.push(name);
+            body.iter().for_each(|s| stmt_tokens(s, tokens));
+        }
+        Stmt::Throw(keyword, value) => {
+            tokens.push(keyword);
+            expr_tokens(value, tokens);
+        }
+        Stmt::Try(keyword, body, catch, finally) => {
+            tokens.push(keyword);
+            body.iter().for_each(|s| stmt_tokens(s, tokens));
+            if let Some((name, handler)) = catch {
+                tokens.push(name);
+                handler.iter().for_each(|s| stmt_tokens(s, tokens));
+            }
+            finally.iter().flatten().for_each(|s| stmt_tokens(s, tokens));
+        }
+        Stmt::VarDeclaration(name, initializer) => {
+            tokens.push(name);
+            initializer.iter().for_each(|e| expr_tokens(e, tokens));
+        }
+        Stmt::While(condition, body) => {
+            expr_tokens(condition, tokens);
+            stmt_tokens(body, tokens);
+        }
+        Stmt::Null => {}
+    }
+}
+
+fn expr_tokens<'a>(expr: &'a Expr, tokens: &mut Vec<&'a Token>) {
+    match expr {
+        Expr::Assign { name, value } => {
+            tokens.push(name);
+            expr_tokens(value, tokens);
+        }
+        Expr::CompoundAssign { name, op, value } => {
+            tokens.push(name);
+            tokens.push(op);
+            expr_tokens(value, tokens);
+        }
+        Expr::Binary { left, op, right } | Expr::Logical { left, op, right } => {
+            expr_tokens(left, tokens);
+            tokens.push(op);
+            expr_tokens(right, tokens);
+        }
+        Expr::Call {
+            callee,
+            paren,
+            arguments,
+        } => {
+            expr_tokens(callee, tokens);
+            arguments.iter().for_each(|a| expr_tokens(a, tokens));
+            tokens.push(paren);
+        }
+        Expr::Get { object, name, .. } => {
+            expr_tokens(object, tokens);
+            tokens.push(name);
+        }
+        Expr::Ternary {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            expr_tokens(condition, tokens);
+            expr_tokens(then_branch, tokens);
+            expr_tokens(else_branch, tokens);
+        }
+        Expr::Grouping { expr } | Expr::OptionalChain { expr } => expr_tokens(expr, tokens),
+        Expr::Literal { token } => tokens.push(token),
+        Expr::Unary { op, expr } => {
+            tokens.push(op);
+            expr_tokens(expr, tokens);
+        }
+        Expr::Variable { name } => tokens.push(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_source;
+
+    #[test]
+    fn test_canonical_spacing_and_indentation() {
+        let source = "var  a=1;\nfun add(x,y){return x+y;}\nif(a==1){print add(a,2);}else print \"no\";\n";
+        let expected = "var a = 1;\nfun add(x, y) {\n    return x + y;\n}\nif (a == 1) {\n    print add(a, 2);\n} else print \"no\";\n";
+        assert_eq!(format_source(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_comments_are_preserved() {
+        let source = "// header\nvar a = 1; // trailing\n\n{\n  // inside\n  print a;\n  // last\n}\n// footer\n";
+        let expected = "// header\nvar a = 1; // trailing\n\n{\n    // inside\n    print a;\n    // last\n}\n// footer\n";
+        assert_eq!(format_source(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_comments_inside_a_statement_move_above_it() {
+        let source = "var x = 1 +\n  // explain the second term\n  2;\nprint x;\n";
+        let expected = "// explain the second term\nvar x = 1 + 2;\nprint x;\n";
+        assert_eq!(format_source(source).unwrap(), expected);
+        assert_eq!(format_source(expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_for_loop_round_trip() {
+        let source = "for (var i = 0; i < 3; i = i + 1) {\n    print i;\n}\nfor (;;) print 1;\n";
+        assert_eq!(format_source(source).unwrap(), source);
+    }
+
+    #[test]
+    fn test_formatting_is_idempotent() {
+        let source = "fun f(n){while(n>0){n=n-1;}\nreturn !(n==0) or false;}\n";
+        let once = format_source(source).unwrap();
+        assert_eq!(format_source(&once).unwrap(), once);
+    }
+
+    #[test]
+    fn test_block_and_doc_comments_are_preserved() {
+        let source = "/// doubles n\nfun double(n) {\n    /* no\n       overflow check */\n    return n * 2;\n}\n";
+        assert_eq!(format_source(source).unwrap(), source);
+    }
+
+    #[test]
+    fn test_operators_round_trip() {
+        let source = "x += 2 ** -y % 3;\nprint ~a & b | c ^ d << 1 >> 2;\nvar p = a?.b.c ?? (d ? e : f);\n";
+        assert_eq!(format_source(source).unwrap(), source);
+    }
+
+    #[test]
+    fn test_imports_round_trip() {
+        let source = "import \"a.lox\";\nimport \"lib/b.lox\" as b;\nimport { x, y } from \"c.lox\";\n";
+        assert_eq!(format_source(source).unwrap(), source);
+    }
+
+    #[test]
+    fn test_comments_after_a_named_import_stay_in_their_block() {
+        let source = "import { x } from \"c.lox\";\nfun f() {\n    // inside\n    print x;\n}\nprint 1;\n";
+        assert_eq!(format_source(source).unwrap(), source);
+    }
+
+    #[test]
+    fn test_try_round_trip() {
+        let source = "try {\n    throw \"x\";\n} catch (e) {\n    print e;\n} finally {\n    print 1;\n}\n";
+        assert_eq!(format_source(source).unwrap(), source);
+    }
+
+    #[test]
+    fn test_parse_error_is_not_formatted() {
+        assert!(format_source("var = ;").is_err());
+    }
+}
@@ -3,17 +3,40 @@ use log::debug;
 use crate::ast::Stmt;
 use crate::environment::Environment;
 use crate::error::Error;
-use crate::interpreter::{Interpreter, Types};
+use crate::interpreter::{Interpreter, Locals, Types};
 use crate::token::Token;
+use std::path::{Path, PathBuf};
 
 pub trait Callable: Debug + Display {
     fn arity(&self) -> usize;
-    fn call(&self, interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, Error>;
+    // how many trailing parameters a call may leave out
+    fn optional(&self) -> usize {
+        0
+    }
+    fn name(&self) -> String;
+    fn call(&self, interpreter: &mut Interpreter, paren: &Token, args: &Vec<Types>) -> Result<Types, Error>;
+    // the `///` comment written above the declaration
+    fn doc(&self) -> Option<String> {
+        None
+    }
+    // the environment a user function closes over, what keeps its variables alive
+    fn closure(&self) -> Option<&Environment> {
+        None
+    }
+    // the script the function was declared in
+    fn file(&self) -> Option<&Path> {
+        None
+    }
 }
 
+//natives report bad arguments with a message, it becomes a runtime error at the call site
+pub type NativeFn = fn(&mut Interpreter, &Vec<Types>) -> Result<Types, String>;
+
 pub struct NativeFunction {
+        pub name: String,
         pub arity:usize,
-        pub body: Box<fn(&Vec<Types>) -> Types>
+        pub optional: usize,
+        pub body: Box<NativeFn>
 }
 
 impl Debug for NativeFunction {
@@ -33,8 +56,20 @@ impl Callable for NativeFunction {
         self.arity
     }
 
-    fn call(&self, _: &mut Interpreter, args: &Vec<Types>) -> Result<Types, Error> {
-        return Ok((self.body)(args));
+    fn optional(&self) -> usize {
+        self.optional
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, paren: &Token, args: &Vec<Types>) -> Result<Types, Error> {
+        // natives charge the values they build themselves, passing one on costs nothing
+        (self.body)(interpreter, args).map_err(|message| Error::RunTime {
+            token: paren.clone(),
+            message,
+        })
     }
 
 }
@@ -45,6 +80,10 @@ pub struct UserFunction {
     pub body: Vec<Stmt>,
     pub closure: Environment,
     pub is_initializer: bool,
+    pub doc: Option<String>,
+    pub file: Option<PathBuf>,
+    // the resolution table of that script, the body's variables are looked up in it
+    pub locals: Locals,
 }
 
 impl Debug for UserFunction{
@@ -65,13 +104,31 @@ impl Callable for UserFunction{
         return self.params.len();
     }
 
-    fn call(&self, interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, Error> {
+    fn name(&self) -> String {
+        self.name.lexeme.clone()
+    }
+
+    fn doc(&self) -> Option<String> {
+        self.doc.clone()
+    }
+
+    fn closure(&self) -> Option<&Environment> {
+        Some(&self.closure)
+    }
+
+    fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, _: &Token, args: &Vec<Types>) -> Result<Types, Error> {
         let environement = Environment::from(self.closure.clone());
 
         for (param, arguments) in self.params.iter().zip(args.iter()){
             environement.define(param.lexeme.clone(), Option::from(arguments.clone()))
         }
-       let is_error= interpreter.execute_block(&self.body, environement);
+       let is_error = interpreter.with_locals(self.locals.clone(), |interpreter| {
+           interpreter.execute_block(&self.body, environement)
+       });
 
         match is_error {
             Err(Error::Return {
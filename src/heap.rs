@@ -0,0 +1,396 @@
+use crate::environment::{Environment, Scope};
+use crate::interpreter::Types;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::mem::size_of;
+use std::rc::{Rc, Weak};
+
+//Approximate accounting of the memory script values hold. New values are charged as they are
+//created, when the total passes the heap limit everything still reachable is measured again.
+//
+//Environments are also tracked for the cycle collector. A closure stored in a variable of the
+//scope it closes over keeps that scope alive through its own `Rc`, so reference counting never
+//frees it. `collect` marks every scope reachable from the roots and clears the rest, which drops
+//the closures inside and breaks the cycles.
+
+//what an environment costs before any variable is defined in it
+pub const ENVIRONMENT_SIZE: usize = size_of::<Environment>() + size_of::<HashMap<String, Types>>();
+
+//one variable slot or collection item, on top of what the value itself points to
+pub const SLOT_SIZE: usize = size_of::<Types>();
+
+//the bytes a newly created value adds, nested lists and maps were charged when they were made
+pub fn shallow_size(value: &Types) -> usize {
+    match value {
+        Types::ReturnString(s) => s.len(),
+        Types::List(items) => items.borrow().iter().map(|item| SLOT_SIZE + text_size(item)).sum(),
+        Types::Map(map) => map
+            .borrow()
+            .iter()
+            .map(|(key, item)| SLOT_SIZE + text_size(key) + text_size(item))
+            .sum(),
+        _ => 0,
+    }
+}
+
+//what storing `value` in a list or map adds, the slot and any text it carries
+pub fn item_size(value: &Types) -> usize {
+    SLOT_SIZE + text_size(value)
+}
+
+pub fn text_size(value: &Types) -> usize {
+    match value {
+        Types::ReturnString(s) => s.len(),
+        _ => 0,
+    }
+}
+
+//adds up everything reachable from the environments it is given, shared values are counted once
+#[derive(Default)]
+pub struct Tracer {
+    seen: HashSet<*const ()>,
+    pub bytes: usize,
+}
+
+impl Tracer {
+    pub fn environment(&mut self, env: &Environment) {
+        let mut env = Some(env);
+        while let Some(current) = env {
+            if !self.seen.insert(current.id()) {
+                return;
+            }
+            self.bytes += ENVIRONMENT_SIZE;
+            current.for_each(|name, value| {
+                self.bytes += SLOT_SIZE + name.len();
+                self.value(value);
+            });
+            env = current.enclosing();
+        }
+    }
+
+    pub fn value(&mut self, value: &Types) {
+        match value {
+            Types::ReturnString(s) => self.bytes += s.len(),
+            Types::List(items) if self.seen.insert(Rc::as_ptr(items) as *const ()) => {
+                for item in items.borrow().iter() {
+                    self.bytes += SLOT_SIZE;
+                    self.value(item);
+                }
+            }
+            Types::Map(map) if self.seen.insert(Rc::as_ptr(map) as *const ()) => {
+                for (key, item) in map.borrow().iter() {
+                    self.bytes += SLOT_SIZE;
+                    self.value(key);
+                    self.value(item);
+                }
+            }
+            Types::Callable(function) => {
+                if let Some(closure) = function.closure() {
+                    self.environment(closure);
+                }
+            }
+            Types::Module(module) => self.environment(module.env()),
+            Types::Error(error) => self.bytes += error.message.len(),
+            _ => {}
+        }
+    }
+}
+
+//collections run once this many environments were created since the last one, or more when
+//more were still alive after it
+const COLLECT_THRESHOLD: usize = 10_000;
+
+//every environment created on this thread, weak so tracking does not keep them alive
+#[derive(Default)]
+struct Registry {
+    scopes: Vec<Weak<Scope>>,
+    created: usize,
+    live_after_collect: usize,
+}
+
+thread_local! {
+    static REGISTRY: RefCell<Registry> = RefCell::new(Registry::default());
+}
+
+//called for each new environment
+pub fn track(env: &Environment) {
+    REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        registry.scopes.push(env.downgrade());
+        registry.created += 1;
+        // freed environments leave their weak handle behind, drop those now and then
+        if registry.scopes.len() >= 2 * COLLECT_THRESHOLD.max(registry.live_after_collect) {
+            registry.scopes.retain(|scope| scope.strong_count() > 0);
+            registry.live_after_collect = registry.scopes.len();
+        }
+    });
+}
+
+pub fn should_collect() -> bool {
+    REGISTRY.with(|registry| {
+        let registry = registry.borrow();
+        registry.created >= COLLECT_THRESHOLD.max(registry.live_after_collect)
+    })
+}
+
+//what the collector has done so far, `gc()` hands it to scripts
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GcStats {
+    pub collections: usize,
+    // environments cleared over all collections
+    pub freed: usize,
+    pub last_freed: usize,
+    // environments still alive after the last collection
+    pub live: usize,
+}
+
+impl Display for GcStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "gc: {} collections, {} environments freed ({} last time), {} live",
+            self.collections, self.freed, self.last_freed, self.live
+        )
+    }
+}
+
+//a scope, list, map, function or module seen while collecting
+#[derive(Default)]
+struct Node {
+    strong: usize,
+    // references to the node from other nodes, the rest come from outside the heap
+    internal: usize,
+    children: Vec<*const ()>,
+    marked: bool,
+}
+
+#[derive(Default)]
+struct Graph {
+    nodes: HashMap<*const (), Node>,
+}
+
+impl Graph {
+    //adds an edge to `child` and reports whether it was seen for the first time
+    fn reference(&mut self, parent: *const (), child: *const (), strong: usize) -> bool {
+        let node = self.nodes.entry(child).or_default();
+        node.internal += 1;
+        let first = node.strong == 0;
+        if first {
+            node.strong = strong;
+        }
+        if let Some(parent) = self.nodes.get_mut(&parent) {
+            parent.children.push(child);
+        }
+        first
+    }
+
+    //every scope is in the graph already, the strong count it was added with is kept
+    fn scope(&mut self, parent: *const (), env: &Environment) {
+        self.reference(parent, env.id(), env.strong_count());
+    }
+
+    //the edges from `parent` to what `value` points to, containers are scanned on first sight
+    fn value(&mut self, parent: *const (), value: &Types) {
+        match value {
+            Types::List(items) => {
+                let id = Rc::as_ptr(items) as *const ();
+                if self.reference(parent, id, Rc::strong_count(items)) {
+                    match items.try_borrow() {
+                        Ok(items) => items.iter().for_each(|item| self.value(id, item)),
+                        // in use right now, it cannot be looked into so it has to stay
+                        Err(_) => self.pin(id),
+                    }
+                }
+            }
+            Types::Map(map) => {
+                let id = Rc::as_ptr(map) as *const ();
+                if self.reference(parent, id, Rc::strong_count(map)) {
+                    match map.try_borrow() {
+                        Ok(map) => map.iter().for_each(|(key, item)| {
+                            self.value(id, key);
+                            self.value(id, item);
+                        }),
+                        Err(_) => self.pin(id),
+                    }
+                }
+            }
+            Types::Callable(function) => {
+                let id = Rc::as_ptr(function) as *const ();
+                if self.reference(parent, id, Rc::strong_count(function)) {
+                    if let Some(closure) = function.closure() {
+                        self.scope(id, closure);
+                    }
+                }
+            }
+            Types::Module(module) => {
+                let id = Rc::as_ptr(module) as *const ();
+                if self.reference(parent, id, Rc::strong_count(module)) {
+                    self.scope(id, module.env());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn pin(&mut self, id: *const ()) {
+        if let Some(node) = self.nodes.get_mut(&id) {
+            node.strong = usize::MAX;
+        }
+    }
+
+    fn mark(&mut self, root: *const ()) {
+        let mut pending = vec![root];
+        while let Some(id) = pending.pop() {
+            if let Some(node) = self.nodes.get_mut(&id) {
+                if !node.marked {
+                    node.marked = true;
+                    pending.extend(node.children.iter().copied());
+                }
+            }
+        }
+    }
+}
+
+//mark and sweep over the tracked environments, returns how many were cleared.
+//Besides `roots`, anything referenced from outside the tracked values (a closure held by the
+//interpreter mid expression, or by the host) counts as a root, so collecting is safe at any point.
+pub fn collect(roots: &[&Environment]) -> usize {
+    let scopes: Vec<Environment> = REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        registry.scopes.retain(|scope| scope.strong_count() > 0);
+        registry.scopes.iter().filter_map(Environment::upgrade).collect()
+    });
+
+    let mut graph = Graph::default();
+    for scope in &scopes {
+        // one of the strong references is the handle upgraded just above
+        graph.nodes.entry(scope.id()).or_default().strong = scope.strong_count() - 1;
+    }
+    for scope in &scopes {
+        let id = scope.id();
+        if let Some(enclosing) = scope.enclosing() {
+            graph.scope(id, enclosing);
+        }
+        if !scope.try_for_each(|_, value| graph.value(id, value)) {
+            graph.pin(id);
+        }
+    }
+
+    let mut marked: Vec<*const ()> = graph
+        .nodes
+        .iter()
+        .filter(|(_, node)| node.strong > node.internal)
+        .map(|(id, _)| *id)
+        .collect();
+    for root in roots {
+        let mut env = Some(*root);
+        while let Some(current) = env {
+            marked.push(current.id());
+            env = current.enclosing();
+        }
+    }
+    for id in marked {
+        graph.mark(id);
+    }
+
+    let mut freed = 0;
+    for scope in &scopes {
+        if !graph.nodes.get(&scope.id()).is_some_and(|node| node.marked) {
+            scope.clear();
+            freed += 1;
+        }
+    }
+    drop(scopes);
+
+    REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        registry.scopes.retain(|scope| scope.strong_count() > 0);
+        registry.live_after_collect = registry.scopes.len();
+        registry.created = 0;
+    });
+    freed
+}
+
+//environments currently tracked and alive
+pub fn live_scopes() -> usize {
+    REGISTRY.with(|registry| {
+        registry
+            .borrow()
+            .scopes
+            .iter()
+            .filter(|scope| scope.strong_count() > 0)
+            .count()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function::UserFunction;
+    use crate::token::{Token, TokenType};
+
+    #[test]
+    fn test_counts_shared_values_once() {
+        let globals = Environment::new();
+        let list = Types::List(Rc::new(RefCell::new(vec![Types::ReturnString("abcd".to_string())])));
+        globals.define("a".to_string(), Some(list.clone()));
+        globals.define("b".to_string(), Some(list.clone()));
+        //the list holds itself, tracing has to stop
+        if let Types::List(items) = &list {
+            items.borrow_mut().push(list.clone());
+        }
+
+        let mut tracer = Tracer::default();
+        tracer.environment(&globals);
+        tracer.environment(&Environment::from(globals.clone()));
+        let expected = 2 * ENVIRONMENT_SIZE + 2 * (SLOT_SIZE + 1) + 2 * SLOT_SIZE + 4;
+        assert_eq!(tracer.bytes, expected);
+
+        if let Types::List(items) = &list {
+            items.borrow_mut().clear();
+        }
+    }
+
+    //a scope holding a function that closes over that same scope
+    fn cycle(globals: &Environment) -> Environment {
+        let scope = Environment::from(globals.clone());
+        let function = UserFunction {
+            name: Token {
+                t_type: TokenType::Identifier("f".to_string()),
+                lexeme: "f".to_string(),
+                line: 1,
+                column: 0,
+            },
+            params: Vec::new(),
+            body: Vec::new(),
+            closure: scope.clone(),
+            is_initializer: false,
+            doc: None,
+            file: None,
+            locals: Default::default(),
+        };
+        scope.define("f".to_string(), Some(Types::Callable(Rc::new(Box::new(function)))));
+        scope
+    }
+
+    #[test]
+    fn test_collect_clears_unreachable_cycles_only() {
+        let globals = Environment::new();
+        let garbage = cycle(&globals).downgrade();
+        let rooted = cycle(&globals);
+        globals.define("kept".to_string(), rooted.get_own("f"));
+        let held = cycle(&globals);
+
+        assert!(garbage.upgrade().is_some(), "the cycle keeps itself alive");
+        assert!(collect(&[&globals]) >= 1);
+        assert!(garbage.upgrade().is_none());
+        // reachable from a root, or from a handle outside the interpreter
+        assert!(rooted.get_own("f").is_some());
+        assert!(held.get_own("f").is_some());
+
+        for env in [&rooted, &held] {
+            env.clear();
+        }
+    }
+}
@@ -1,24 +1,219 @@
 use crate::ast::{Expr, Stmt, Visitor};
 use crate::class::Class;
+use crate::debugger::DebugHook;
 use crate::environment::Environment;
-use crate::error::Error;
+use crate::error::{Error, ErrorValue};
 use crate::error::Error::InvalidStmt;
 use crate::function::{Callable, NativeFunction, UserFunction};
+use crate::heap::{self, GcStats, Tracer};
+use crate::map::Map;
+use crate::module::{self, Module};
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::stdlib;
+use crate::stdlib::math::Random;
 use crate::token::{Token, TokenType};
 use std::cell::RefCell;
 use std::collections::HashMap;
 
 use std::fmt::{write, Debug, Display, Formatter, Result as FmtResult};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::fs;
 use std::string::String;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+//how far out the resolver found each variable use, one table per script or module
+pub type Locals = Rc<HashMap<Token, usize>>;
 
 //represents an Interpreter struct
-#[derive(Debug)]
 pub struct Interpreter {
     pub globals: Environment,
     environment: Environment,
-    locals: HashMap<Token, usize>,
+    // the table of the file whose code is running, swapped by imports and calls
+    locals: Locals,
+    tests: Vec<TestCase>,
+    // where `print` and the I/O natives write to, stdout unless the host swaps it
+    out: Box<dyn Write>,
+    // imported files by canonical path, each one is only run once
+    modules: HashMap<PathBuf, Rc<Module>>,
+    // the script being run and the modules it is importing, innermost last
+    scripts: Vec<PathBuf>,
+    // state behind `random()`, reseeded by `seed_random(n)`
+    pub random: Random,
+    // what scripts are allowed to touch outside the interpreter, nothing by default
+    pub capabilities: Capabilities,
+    // where `read_line()` reads from, stdin unless the host swaps it
+    input: Box<dyn BufRead>,
+    // what `args()` returns, the command line after the script path
+    script_args: Vec<String>,
+    limits: Limits,
+    // statements executed since the limits were set
+    steps: u64,
+    // the script, then every call and import in progress, innermost last
+    call_stack: Vec<CallFrame>,
+    deadline: Option<Instant>,
+    // environments of the blocks and calls that are suspended while an inner one runs
+    frames: Vec<Environment>,
+    // approximate bytes held by script values, see `allocate`
+    heap_bytes: usize,
+    gc_stats: GcStats,
+    // told about every statement before it runs, set for `rlox debug`
+    debugger: Option<Box<dyn DebugHook>>,
+    // unresolved names are searched through every scope, for expressions a debugger evaluates
+    dynamic_lookup: bool,
+}
+
+//a function call or module import in progress, the script itself is the outermost frame
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    pub name: String,
+    pub file: Option<PathBuf>,
+    // the line of the statement running in this frame
+    pub line: u32,
+    // the scope the frame was in when it made the call running above it
+    pub environment: Option<Environment>,
+}
+
+//how deep calls may nest unless the host says otherwise, each call takes tens of kilobytes of
+//native stack in debug builds so the thread running the interpreter needs a large one
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+//the clock is only read once every this many statements
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+//Resource limits for running untrusted code, `None` means unlimited
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    // statements the script may execute in total
+    pub max_steps: Option<u64>,
+    // calls that may be in progress at once, going deeper is a "Stack overflow." runtime error
+    pub max_call_depth: Option<usize>,
+    // wall clock time the script may run for
+    pub timeout: Option<Duration>,
+    // approximate bytes script values may hold, going over is a catchable runtime error
+    pub max_heap: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_steps: None,
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            timeout: None,
+            max_heap: None,
+        }
+    }
+}
+
+//Capabilities are off by default so embedded scripts stay sandboxed, the CLI turns them on with flags
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Capabilities {
+    // file and stdin natives, `--allow-fs`
+    pub fs: bool,
+    // reading environment variables with `getenv`, always on for the CLI
+    pub env: bool,
+    // loading other scripts from disk with `import`, always on for the CLI
+    pub imports: bool,
+}
+
+impl Debug for Interpreter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Interpreter")
+            .field("globals", &self.globals)
+            .field("environment", &self.environment)
+            .field("locals", &self.locals)
+            .field("tests", &self.tests)
+            .field("modules", &self.modules)
+            .field("scripts", &self.scripts)
+            .field("random", &self.random)
+            .field("capabilities", &self.capabilities)
+            .field("limits", &self.limits)
+            .finish_non_exhaustive()
+    }
+}
+
+//an output sink that can be handed to the interpreter and read back afterwards, for tests
+#[cfg(test)]
+#[derive(Clone, Debug, Default)]
+pub struct CapturedOutput(Rc<RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl CapturedOutput {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+#[cfg(test)]
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+//`assert_raises(f)` passes when calling `f` raises a runtime error or a throw, anything else
+//unwinding out of the call, like `exit` or a limit, carries on unchanged
+struct AssertRaises;
+
+impl Callable for AssertRaises {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> String {
+        "assert_raises".to_string()
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, paren: &Token, args: &Vec<Types>) -> Result<Types, Error> {
+        let failed = |message: String| Error::RunTime {
+            token: paren.clone(),
+            message,
+        };
+        let function = match &args[0] {
+            Types::Callable(function) if function.arity() == 0 => function.clone(),
+            other => {
+                return Err(failed(format!(
+                    "assert_raises expects a function without parameters, got {}.",
+                    other
+                )))
+            }
+        };
+        match interpreter.call_function(&function, paren, &vec![]) {
+            Err(Error::RunTime { .. }) | Err(Error::Throw { .. }) => Ok(Types::Nil),
+            Err(other) => Err(other),
+            Ok(value) => Err(failed(format!(
+                "assert_raises failed: expected an error, got {}.",
+                value
+            ))),
+        }
+    }
+}
+
+impl Debug for AssertRaises {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "<native func>")
+    }
+}
+
+impl Display for AssertRaises {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "<native func>")
+    }
+}
+
+#[derive(Debug)]
+pub struct TestCase {
+    pub name: String,
+    pub body: Vec<Stmt>,
+    closure: Environment,
 }
 
 macro_rules! istruthy {
@@ -33,35 +228,564 @@ macro_rules! istruthy {
 impl Interpreter {
     //Does nothing for now?
     pub fn new() -> Self {
+        Self::with_output(Box::new(io::stdout()))
+    }
+
+    //an interpreter whose `print` output goes to `out` instead of stdout
+    pub fn with_output(out: Box<dyn Write>) -> Self {
         let globals = Environment::new();
 
         let clock: Types = Types::Callable(Rc::new(Box::new(NativeFunction {
+            name: "clock".to_string(),
             arity: 0,
-            body: Box::new(|args: &Vec<Types>| {
-                Types::Number(
+            optional: 0,
+            body: Box::new(|_: &mut Interpreter, args: &Vec<Types>| {
+                Ok(Types::Number(
                     SystemTime::now()
                         .duration_since(UNIX_EPOCH)
                         .expect("Could not retrieve time.")
                         .as_millis() as f64,
-                )
+                ))
             }),
         })));
 
         let asset_eql = Types::Callable(Rc::new(Box::new(NativeFunction {
+            name: "assert".to_string(),
             arity: 2,
-            body: Box::new(|args: &Vec<Types>| {
-                Types::Boolean(args[0].clone().to_string() == args[1].clone().to_string())
+            optional: 0,
+            body: Box::new(|_: &mut Interpreter, args: &Vec<Types>| {
+                Ok(Types::Boolean(args[0].equals(&args[1])))
+            }),
+        })));
+        let help = Types::Callable(Rc::new(Box::new(NativeFunction {
+            name: "help".to_string(),
+            arity: 1,
+            optional: 0,
+            body: Box::new(|interpreter: &mut Interpreter, args: &Vec<Types>| {
+                let doc = match &args[0] {
+                    Types::Callable(function) => function.doc(),
+                    Types::Class(class) => class.borrow().doc.clone(),
+                    _ => None,
+                };
+                match doc {
+                    Some(doc) => stdlib::created(interpreter, Types::ReturnString(doc)),
+                    None => Ok(Types::Nil),
+                }
+            }),
+        })));
+        let int = Types::Callable(Rc::new(Box::new(NativeFunction {
+            name: "int".to_string(),
+            arity: 1,
+            optional: 0,
+            body: Box::new(|_: &mut Interpreter, args: &Vec<Types>| match &args[0] {
+                Types::Int(n) => Ok(Types::Int(*n)),
+                Types::Number(n) if n.is_finite() && n.abs() < 9.2e18 => Ok(Types::Int(*n as i64)),
+                Types::ReturnString(s) => s
+                    .trim()
+                    .parse::<i64>()
+                    .map(Types::Int)
+                    .map_err(|_| format!("Cannot convert \"{}\" to an int.", s)),
+                other => Err(format!("Cannot convert {} to an int.", other)),
+            }),
+        })));
+        let float = Types::Callable(Rc::new(Box::new(NativeFunction {
+            name: "float".to_string(),
+            arity: 1,
+            optional: 0,
+            body: Box::new(|_: &mut Interpreter, args: &Vec<Types>| match &args[0] {
+                Types::Int(n) => Ok(Types::Number(*n as f64)),
+                Types::Number(n) => Ok(Types::Number(*n)),
+                Types::ReturnString(s) => s
+                    .trim()
+                    .parse::<f64>()
+                    .map(Types::Number)
+                    .map_err(|_| format!("Cannot convert \"{}\" to a float.", s)),
+                other => Err(format!("Cannot convert {} to a float.", other)),
+            }),
+        })));
+        let assert_eq = Types::Callable(Rc::new(Box::new(NativeFunction {
+            name: "assert_eq".to_string(),
+            arity: 2,
+            optional: 0,
+            body: Box::new(|_: &mut Interpreter, args: &Vec<Types>| {
+                if args[0].equals(&args[1]) {
+                    Ok(Types::Nil)
+                } else {
+                    Err(format!(
+                        "assert_eq failed: expected {}, got {}.",
+                        args[1], args[0]
+                    ))
+                }
             }),
         })));
+        let assert_true = Types::Callable(Rc::new(Box::new(NativeFunction {
+            name: "assert_true".to_string(),
+            arity: 1,
+            optional: 0,
+            body: Box::new(|_: &mut Interpreter, args: &Vec<Types>| {
+                if istruthy!(&args[0]) {
+                    Ok(Types::Nil)
+                } else {
+                    Err(format!("assert_true failed: got {}.", args[0]))
+                }
+            }),
+        })));
+        let assert_raises = Types::Callable(Rc::new(Box::new(AssertRaises)));
         globals.define("assert".to_string(), Some(asset_eql));
+        globals.define("assert_eq".to_string(), Some(assert_eq));
+        globals.define("assert_true".to_string(), Some(assert_true));
+        globals.define("assert_raises".to_string(), Some(assert_raises));
+        globals.define("help".to_string(), Some(help));
         globals.define("clock".to_string(), Some(clock));
+        globals.define("int".to_string(), Some(int));
+        globals.define("float".to_string(), Some(float));
+        stdlib::register(&globals);
         Self {
             globals: globals.clone(),
             environment: globals.clone(),
-            locals: HashMap::new(),
+            locals: Locals::default(),
+            tests: Vec::new(),
+            out,
+            modules: HashMap::new(),
+            scripts: Vec::new(),
+            random: Random::from_time(),
+            capabilities: Capabilities::default(),
+            input: Box::new(io::BufReader::new(io::stdin())),
+            script_args: Vec::new(),
+            limits: Limits::default(),
+            steps: 0,
+            call_stack: vec![CallFrame {
+                name: "<script>".to_string(),
+                file: None,
+                line: 0,
+                environment: None,
+            }],
+            deadline: None,
+            frames: Vec::new(),
+            heap_bytes: 0,
+            gc_stats: GcStats::default(),
+            debugger: None,
+            dynamic_lookup: false,
         }
     }
 
+    //the file being run, imports are resolved relative to it
+    pub fn set_script_path(&mut self, path: &Path) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.call_stack[0].file = Some(path.clone());
+        self.scripts = vec![path];
+    }
+
+    //loads, resolves and runs the file `path` names once, later imports get the cached module
+    fn import_module(&mut self, path: &Token) -> Result<Rc<Module>, Error> {
+        let import_path = module::import_path(path);
+        let error = |message: String| Error::RunTime {
+            token: path.clone(),
+            message,
+        };
+        if !self.capabilities.imports {
+            return Err(error(format!(
+                "Could not import '{}': loading modules is disabled.",
+                import_path
+            )));
+        }
+        let base = match self.scripts.last().and_then(|script| script.parent()) {
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::new(),
+        };
+        let file = fs::canonicalize(base.join(&import_path))
+            .map_err(|e| error(format!("Could not read module '{}': {}.", import_path, e)))?;
+
+        if let Some(module) = self.modules.get(&file) {
+            return Ok(module.clone());
+        }
+        if let Some(start) = self.scripts.iter().position(|script| script == &file) {
+            let cycle: Vec<String> = self.scripts[start..]
+                .iter()
+                .chain(Some(&file))
+                .map(|script| module::display_name(script))
+                .collect();
+            return Err(error(format!("Import cycle: {}.", cycle.join(" -> "))));
+        }
+
+        let source = fs::read_to_string(&file)
+            .map_err(|e| error(format!("Could not read module '{}': {}.", import_path, e)))?;
+        let mut scanner = Scanner::new(source.as_bytes());
+        let mut parser = Parser::new(scanner.scan_tokens());
+        let statements = parser.parse()?;
+        if scanner.had_error() || parser.had_error() {
+            return Err(error(format!("Could not compile module '{}'.", import_path)));
+        }
+
+        // module top-levels get their own scope, globals stay visible underneath it
+        let env = Environment::module(self.globals.clone());
+        self.with_locals(Locals::default(), |interpreter| {
+            let mut resolver = Resolver::new(interpreter);
+            resolver.resolve_stmts(&statements);
+            if resolver.had_error() {
+                return Err(error(format!("Could not compile module '{}'.", import_path)));
+            }
+
+            interpreter.scripts.push(file.clone());
+            interpreter.push_frame(CallFrame {
+                name: format!("<module {}>", module::display_name(&file)),
+                file: Some(file.clone()),
+                line: 0,
+                environment: None,
+            });
+            let result = interpreter.execute_block(&statements, env.clone());
+            interpreter.pop_frame();
+            interpreter.scripts.pop();
+            result
+        })?;
+
+        let name = module::binding_name(path).lexeme;
+        let module = Rc::new(Module::new(name, file.clone(), env));
+        self.modules.insert(file, module.clone());
+        Ok(module)
+    }
+
+    //the sink script output is written to
+    pub fn output(&mut self) -> &mut dyn Write {
+        &mut *self.out
+    }
+
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.script_args = args;
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.script_args
+    }
+
+    pub fn set_debugger(&mut self, debugger: Box<dyn DebugHook>) {
+        self.debugger = Some(debugger);
+    }
+
+    pub fn call_stack(&self) -> &[CallFrame] {
+        &self.call_stack
+    }
+
+    //calls in progress, 0 while the script's own top level runs
+    pub fn call_depth(&self) -> usize {
+        self.call_stack.len() - 1
+    }
+
+    //evaluates the arguments and calls `callee` with them
+    fn call_value(&mut self, callee: Types, paren: &Token, arguments: &[Expr]) -> Result<Types, Error> {
+        let arguments: Result<Vec<Types>, Error> = arguments
+            .iter()
+            .map(|e| self.visit_expression(e))
+            .collect();
+        let args = arguments?;
+
+        if let Types::Callable(function) = callee {
+            let args_len = args.len();
+            let required = function.arity() - function.optional();
+            if args_len < required || args_len > function.arity() {
+                let expected = if required == function.arity() {
+                    function.arity().to_string()
+                } else {
+                    format!("{} to {}", required, function.arity())
+                };
+                Err(Error::RunTime {
+                    token: paren.clone(),
+                    message: format!(
+                        "Expected {} arguments but got {}.",
+                        expected, args_len
+                    ),
+                })
+            } else {
+                self.call_function(&function, paren, &args)
+            }
+        } else {
+            Err(Error::RunTime {
+                token: paren.clone(),
+                message: "Can only call functions and classes.".to_string(),
+            })
+        }
+    }
+
+    //reads `name` off an already evaluated object
+    fn get_property(&self, object: Types, name: &Token) -> Result<Types, Error> {
+        match object {
+            Types::Module(module) => module.get(name),
+            Types::Map(map) => map.borrow().get(&name.lexeme).cloned().ok_or_else(|| {
+                Error::RunTime {
+                    token: name.clone(),
+                    message: format!("Map has no key '{}'.", name.lexeme),
+                }
+            }),
+            Types::Error(error) => match name.lexeme.as_str() {
+                "message" => Ok(Types::ReturnString(error.message.clone())),
+                "line" => Ok(Types::Int(error.line as i64)),
+                _ => Err(Error::RunTime {
+                    token: name.clone(),
+                    message: "Errors only have 'message' and 'line' properties.".to_string(),
+                }),
+            },
+            Types::Callable(function) => match name.lexeme.as_str() {
+                "arity" => Ok(Types::Int(function.arity() as i64)),
+                "name" => Ok(Types::ReturnString(function.name())),
+                _ => Err(Error::RunTime {
+                    token: name.clone(),
+                    message: "Functions only have 'arity' and 'name' properties.".to_string(),
+                }),
+            },
+            _ => Err(Error::RunTime {
+                token: name.clone(),
+                message: "Only instances have properties.".to_string(),
+            }),
+        }
+    }
+
+    //evaluates the gets and calls of an optional chain, `None` once a `?.` met nil and the
+    //rest of the chain was skipped
+    fn chain(&mut self, expr: &Expr) -> Result<Option<Types>, Error> {
+        match expr {
+            Expr::Get {
+                object,
+                name,
+                optional,
+            } => match self.chain(object)? {
+                Some(Types::Nil) if *optional => Ok(None),
+                Some(object) => self.get_property(object, name).map(Some),
+                None => Ok(None),
+            },
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => match self.chain(callee)? {
+                Some(callee) => self.call_value(callee, paren, arguments).map(Some),
+                None => Ok(None),
+            },
+            other => self.visit_expression(other).map(Some),
+        }
+    }
+
+    //calls with arguments already checked against the arity, the call counts towards the depth limit
+    pub fn call_function(
+        &mut self,
+        function: &Rc<Box<dyn Callable>>,
+        paren: &Token,
+        args: &Vec<Types>,
+    ) -> Result<Types, Error> {
+        if self.limits.max_call_depth.is_some_and(|max| self.call_depth() >= max) {
+            return Err(Error::RunTime {
+                token: paren.clone(),
+                message: "Stack overflow.".to_string(),
+            });
+        }
+        // natives have no file of their own and count as part of their caller's
+        let file = match function.file() {
+            Some(file) => Some(file.to_path_buf()),
+            None => self.current_file().map(Path::to_path_buf),
+        };
+        self.push_frame(CallFrame {
+            name: function.name(),
+            file,
+            line: paren.line,
+            environment: None,
+        });
+        let result = function.call(self, paren, args);
+        self.pop_frame();
+        result
+    }
+
+    fn push_frame(&mut self, frame: CallFrame) {
+        if let Some(caller) = self.call_stack.last_mut() {
+            caller.environment = Some(self.environment.clone());
+        }
+        self.call_stack.push(frame);
+    }
+
+    fn pop_frame(&mut self) {
+        self.call_stack.pop();
+        if let Some(caller) = self.call_stack.last_mut() {
+            caller.environment = None;
+        }
+    }
+
+    //the scope frame `index` of the call stack is in, the innermost frame is in the current one
+    pub fn frame_environment(&self, index: usize) -> Option<&Environment> {
+        if index + 1 == self.call_stack.len() {
+            Some(&self.environment)
+        } else {
+            self.call_stack.get(index)?.environment.as_ref()
+        }
+    }
+
+    //the file of the code running now, `None` for source that did not come from a file
+    pub fn current_file(&self) -> Option<&Path> {
+        self.call_stack.last().and_then(|frame| frame.file.as_deref())
+    }
+
+    //the innermost scope of the code running now
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    //evaluates an expression that was never resolved, such as one typed into the debugger,
+    //its names are looked up through every scope visible from the current one
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Types, Error> {
+        let previous = std::mem::replace(&mut self.dynamic_lookup, true);
+        let result = self.visit_expression(expr);
+        self.dynamic_lookup = previous;
+        result
+    }
+
+    //like `evaluate`, in the scope of a frame further down the call stack
+    pub fn evaluate_in(&mut self, expr: &Expr, env: Environment) -> Result<Types, Error> {
+        let previous = std::mem::replace(&mut self.environment, env);
+        let result = self.evaluate(expr);
+        self.environment = previous;
+        result
+    }
+
+    //records where the current frame is and gives the debugger its turn
+    fn before_statement(&mut self, line: u32) -> Result<(), Error> {
+        if let Some(frame) = self.call_stack.last_mut() {
+            frame.line = line;
+        }
+        // taken out for the call, so whatever the debugger evaluates runs without stopping
+        match self.debugger.take() {
+            Some(mut debugger) => {
+                let result = debugger.statement(self, line);
+                self.debugger = Some(debugger);
+                result
+            }
+            None => Ok(()),
+        }
+    }
+
+    //the step count and the timeout start over from here
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+        self.steps = 0;
+        self.deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    //counts one executed statement against the limits
+    fn tick(&mut self) -> Result<(), Error> {
+        self.steps += 1;
+        if let Some(max) = self.limits.max_steps {
+            if self.steps > max {
+                return Err(Error::Limit {
+                    message: format!("Step limit of {} exceeded.", max),
+                });
+            }
+        }
+        if let (Some(deadline), Some(timeout)) = (self.deadline, self.limits.timeout) {
+            if self.steps.is_multiple_of(DEADLINE_CHECK_INTERVAL) && Instant::now() >= deadline {
+                return Err(Error::Limit {
+                    message: format!("Timeout of {}ms exceeded.", timeout.as_millis()),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    //charges newly created values against the heap limit, when it is passed what is still
+    //reachable is measured again before giving up with an out of memory error
+    pub fn allocate(&mut self, bytes: usize, token: &Token) -> Result<(), Error> {
+        self.charge(bytes).map_err(|message| Error::RunTime {
+            token: token.clone(),
+            message,
+        })
+    }
+
+    //`allocate` for natives, which report errors as a message
+    pub fn charge(&mut self, bytes: usize) -> Result<(), String> {
+        self.heap_bytes = self.heap_bytes.saturating_add(bytes);
+        match self.limits.max_heap {
+            Some(max) if self.heap_bytes > max => {
+                // the new value is not stored anywhere yet, so the trace cannot see it
+                self.collect_garbage();
+                let live = self.measure_heap();
+                if live.saturating_add(bytes) > max {
+                    return Err(format!("Out of memory: heap limit of {} bytes exceeded.", max));
+                }
+                self.heap_bytes = live + bytes;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    //checks a value of `bytes` would fit before a native builds it, the value is only charged
+    //once it is returned
+    pub fn reserve(&mut self, bytes: usize) -> Result<(), String> {
+        let result = self.charge(bytes);
+        self.heap_bytes = self.heap_bytes.saturating_sub(bytes);
+        result
+    }
+
+    //the environments everything the script can still reach hangs off
+    fn roots(&self) -> Vec<&Environment> {
+        let mut roots = vec![&self.globals, &self.environment];
+        roots.extend(self.frames.iter());
+        roots.extend(self.modules.values().map(|module| module.env()));
+        roots.extend(self.tests.iter().map(|test| &test.closure));
+        roots
+    }
+
+    //the bytes held by everything reachable from the running script, `mem_usage()`
+    pub fn measure_heap(&mut self) -> usize {
+        let mut tracer = Tracer::default();
+        for root in self.roots() {
+            tracer.environment(root);
+        }
+        self.heap_bytes = tracer.bytes;
+        self.heap_bytes
+    }
+
+    //frees the environments only kept alive by closure cycles, returns how many there were
+    pub fn collect_garbage(&mut self) -> usize {
+        let freed = heap::collect(&self.roots());
+        self.gc_stats.collections += 1;
+        self.gc_stats.freed += freed;
+        self.gc_stats.last_freed = freed;
+        self.gc_stats.live = heap::live_scopes();
+        freed
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.gc_stats
+    }
+
+    //makes `env` the current environment until `run` returns, the suspended one stays a root
+    fn with_environment<T>(&mut self, env: Environment, run: impl FnOnce(&mut Self) -> T) -> T {
+        self.heap_bytes += heap::ENVIRONMENT_SIZE;
+        if heap::should_collect() {
+            self.collect_garbage();
+        }
+        let previous = std::mem::replace(&mut self.environment, env);
+        self.frames.push(previous);
+        let result = run(self);
+        self.environment = self.frames.pop().expect("the frame pushed above");
+        result
+    }
+
+    //replaces stdin as the source of `read_line()`
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        self.input = input;
+    }
+
+    pub fn input(&mut self) -> &mut dyn BufRead {
+        &mut *self.input
+    }
+
+    //the `test "name" { }` blocks seen so far, `rlox test` runs them after the script
+    pub fn take_tests(&mut self) -> Vec<TestCase> {
+        std::mem::take(&mut self.tests)
+    }
+
+    //runs one test body in a scope of its own
+    pub fn run_test(&mut self, test: &TestCase) -> Result<(), Error> {
+        self.execute_block(&test.body, Environment::from(test.closure.clone()))
+    }
+
     // for now it returns Executed types
     pub fn interpret(&mut self, statement: Vec<Stmt>) -> Result<(), Error> {
         for x in statement {
@@ -70,43 +794,94 @@ impl Interpreter {
         Ok(())
     }
 
-    fn stringify(&self, types: Types) -> String {
+    pub fn stringify(&self, types: Types) -> String {
         match types {
             Types::Boolean(b) => b.to_string(),
             Types::Nil => "nil".to_string(),
-            Types::Number(n) => n.to_string(),
+            Types::Number(n) => format_number(n),
+            Types::Int(n) => n.to_string(),
             Types::ReturnString(s) => s,
             Types::Callable(f) => f.to_string(),
             Types::Class(c) => c.borrow().to_string(),
+            Types::Module(m) => m.to_string(),
+            list @ Types::List(_) => list.to_string(),
+            map @ Types::Map(_) => map.to_string(),
+            Types::Error(e) => e.message.clone(),
         }
     }
 
     pub fn execute_block(&mut self, statements: &Vec<Stmt>, env: Environment) -> Result<(), Error> {
-        let previous = self.environment.clone();
-        let steps = || -> Result<(), Error> {
-            self.environment = env;
-
+        self.with_environment(env, |interpreter| {
             for statement in statements {
-                self.visit_statement(&statement)?
+                interpreter.visit_statement(statement)?
             }
             Ok(())
-        };
-        let result = steps();
-        self.environment = previous;
-        result
+        })
+    }
+
+    //runs a for loop inside the environment already set up for its initializer
+    fn execute_for(
+        &mut self,
+        initializer: &Option<Box<Stmt>>,
+        condition: &Option<Expr>,
+        increment: &Option<Expr>,
+        body: &Stmt,
+    ) -> Result<(), Error> {
+        if let Some(init) = initializer {
+            self.visit_statement(init)?;
+        }
+        loop {
+            if let Some(cond) = condition {
+                if !istruthy!(self.visit_expression(cond)?) {
+                    break;
+                }
+            }
+            self.visit_statement(body)?;
+            if let Some(inc) = increment {
+                self.visit_expression(inc)?;
+            }
+        }
+        Ok(())
     }
 
     pub fn resolve(&mut self, name: &Token, depth: usize) {
-        self.locals.insert(name.clone(), depth);
+        Rc::make_mut(&mut self.locals).insert(name.clone(), depth);
+    }
+
+    //resolves and runs with the table of another file until `run` returns,
+    //code that was resolved is looked up through its table even when a debugger expression calls it
+    pub fn with_locals<T>(&mut self, locals: Locals, run: impl FnOnce(&mut Self) -> T) -> T {
+        let previous = std::mem::replace(&mut self.locals, locals);
+        let dynamic_lookup = std::mem::replace(&mut self.dynamic_lookup, false);
+        let result = run(self);
+        self.locals = previous;
+        self.dynamic_lookup = dynamic_lookup;
+        result
+    }
+
+    fn assign_variable(&mut self, name: &Token, value: &Types) -> Result<(), Error> {
+        if self.dynamic_lookup {
+            return self.environment.assign(name, value);
+        }
+        if let Some(distance) = self.locals.get(name) {
+            self.environment.assign_at(name, value, distance)
+        } else {
+            self.environment.assign_top_level(name, value)
+        }
     }
 
     pub fn lookup_variable(&mut self, name: &Token) -> Result<Types, Error> {
+        // an unresolved expression's tokens can collide with ones in the table, so skip it
+        if self.dynamic_lookup {
+            return self.environment.get(name);
+        }
         let distance = self.locals.get(name);
 
         if let Some(dist) = distance {
             self.environment.get_at(dist, name)
         } else {
-            self.globals.get(name)
+            // unresolved names are top-level ones, those of a module or the globals
+            self.environment.get_top_level(name)
         }
     }
 }
@@ -114,14 +889,18 @@ impl Visitor for Interpreter {
     type E = Result<Types, Error>;
     type S = Result<(), Error>;
     fn visit_statement(&mut self, s: &Stmt) -> Self::S {
+        self.tick()?;
+        if let Some(line) = s.line() {
+            self.before_statement(line)?;
+        }
         match s {
             &Stmt::Block(ref stmts) => {
                 self.execute_block(stmts, Environment::from(self.environment.clone()))?;
                 Ok(())
             }
-            &Stmt::Class(ref token, ref stmts) => {
+            &Stmt::Class(ref token, ref stmts, ref doc) => {
                 self.environment.define(token.lexeme.clone(), None);
-                let class = Class::new(token.lexeme.clone());
+                let class = Class::new(token.lexeme.clone(), doc.clone());
                 self.environment
                     .assign(token, &Types::Class(Rc::new(RefCell::new(class))))?;
                 Ok(())
@@ -131,13 +910,23 @@ impl Visitor for Interpreter {
                 Ok(())
             }
 
-            &Stmt::Function(ref name_token, ref parameters, ref body) => {
+            &Stmt::For(ref initializer, ref condition, ref increment, ref body) => {
+                let env = Environment::from(self.environment.clone());
+                self.with_environment(env, |interpreter| {
+                    interpreter.execute_for(initializer, condition, increment, body)
+                })
+            }
+
+            &Stmt::Function(ref name_token, ref parameters, ref body, ref doc) => {
                 let user_function = Types::Callable(Rc::new(Box::new(UserFunction {
                     name: name_token.clone(),
                     params: parameters.clone(),
                     body: body.clone(),
                     closure: self.environment.clone(),
                     is_initializer: false,
+                    doc: doc.clone(),
+                    file: self.current_file().map(Path::to_path_buf),
+                    locals: self.locals.clone(),
                 })));
 
                 self.environment
@@ -145,6 +934,28 @@ impl Visitor for Interpreter {
 
                 Ok(())
             }
+            &Stmt::Import(ref path, ref alias, ref names) => {
+                let module = self.import_module(path)?;
+                if names.is_empty() {
+                    let name = alias.clone().unwrap_or_else(|| module::binding_name(path));
+                    self.environment
+                        .define(name.lexeme, Some(Types::Module(module.clone())));
+                }
+                for name in names {
+                    let value = module.get(name)?;
+                    self.environment.define(name.lexeme.clone(), Some(value));
+                }
+                Ok(())
+            }
+            &Stmt::Test(ref name, ref body) => {
+                // only registered here, the test runner decides when to run it
+                self.tests.push(TestCase {
+                    name: name.to_string(),
+                    body: body.clone(),
+                    closure: self.environment.clone(),
+                });
+                Ok(())
+            }
             &Stmt::Return(ref Token, ref expr) => {
                 let return_value = expr
                     .clone()
@@ -155,6 +966,29 @@ impl Visitor for Interpreter {
                     value: return_value,
                 })
             }
+            &Stmt::Throw(ref keyword, ref value) => Err(Error::Throw {
+                token: keyword.clone(),
+                value: self.visit_expression(value)?,
+            }),
+            &Stmt::Try(_, ref body, ref catch, ref finally) => {
+                let result = self.execute_block(body, Environment::from(self.environment.clone()));
+                let result = match (result, catch) {
+                    (Err(error), Some((name, handler))) => match error.caught_value() {
+                        Ok(value) => {
+                            let env = Environment::from(self.environment.clone());
+                            env.define(name.lexeme.clone(), Some(value));
+                            self.execute_block(handler, env)
+                        }
+                        Err(error) => Err(error),
+                    },
+                    (result, _) => result,
+                };
+                // runs on every way out of the try, an error inside it replaces the original outcome
+                if let Some(finally) = finally {
+                    self.execute_block(finally, Environment::from(self.environment.clone()))?;
+                }
+                result
+            }
             &Stmt::IfStmt(ref Expr, ref then, ref else_option) => {
                 if istruthy!(&self.visit_expression(Expr)?) {
                     self.visit_statement(then)?;
@@ -168,7 +1002,9 @@ impl Visitor for Interpreter {
 
             Stmt::Print(Expr) => {
                 let e = self.visit_expression(Expr)?;
-                println!("{}", self.stringify(e));
+                let text = self.stringify(e);
+                // like println!, but a sink that went away (e.g. a closed pipe) does not abort the script
+                let _ = writeln!(self.out, "{}", text);
                 Ok(())
             }
 
@@ -179,6 +1015,7 @@ impl Visitor for Interpreter {
                         _ => String::from(""),
                     };
                     let result = self.visit_expression(&e)?;
+                    self.allocate(heap::SLOT_SIZE + var_name.len(), Token)?;
                     Ok(self.environment.define(var_name, Some(result)))
                 }
                 &None => {
@@ -209,14 +1046,29 @@ impl Visitor for Interpreter {
                 ..
             } => {
                 let new_value = self.visit_expression(value)?;
-                if let Some(distance) = self.locals.get(name) {
-                    self.environment.assign_at(name, &new_value, distance)?;
-                } else {
-                    self.environment.assign(name, &new_value)?;
-                }
+                self.assign_variable(name, &new_value)?;
 
                 return Ok(new_value);
             }
+            &Expr::CompoundAssign {
+                ref name,
+                ref op,
+                ref value,
+            } => {
+                let current = self.lookup_variable(name)?;
+                let right = self.visit_expression(value)?;
+                let base_op = Token {
+                    t_type: compound_operator(&op.t_type),
+                    lexeme: op.lexeme.clone(),
+                    line: op.line,
+                    column: 0,
+                };
+                let new_value = binary(current, &base_op, right)?;
+                self.allocate(heap::shallow_size(&new_value), op)?;
+                self.assign_variable(name, &new_value)?;
+
+                Ok(new_value)
+            }
             &Expr::Binary {
                 ref left,
                 ref op,
@@ -226,74 +1078,36 @@ impl Visitor for Interpreter {
                 let left_expr = self.visit_expression(left)?;
                 let right_expr = self.visit_expression(right)?;
 
-                return match (left_expr, op.clone(), right_expr) {
-                    // For Strings
-                    (Types::ReturnString(ls), t, Types::ReturnString(rs)) => match t.t_type.clone()
-                    {
-                        TokenType::Plus => {
-                            Ok(Types::ReturnString(String::from(format!("{}{}", ls, rs))))
-                        }
-                        _ => Err(Error::RunTime {
-                            token: t.clone(),
-                            message: "Operands must be two numbers or two strings".to_string(),
-                        }),
-                    },
-                    //For number basic operation and comparison
-                    (Types::Number(ln), t, Types::Number(rn)) => match t.t_type {
-                        TokenType::Plus => Ok(Types::Number(ln + rn)),
-                        TokenType::Minus => Ok(Types::Number(ln - rn)),
-                        TokenType::Star => Ok(Types::Number(ln - rn)),
-                        TokenType::Slash => {
-                            if rn == 0.0 {
-                                Err(Error::RunTime {
-                                    token: t.clone(),
-                                    message: "Operands must be two numbers or two strings"
-                                        .to_string(),
-                                })
-                            } else {
-                                Ok(Types::Number(ln / rn))
-                            }
-                        }
-                        TokenType::Greater => Ok(Types::Boolean(ln > rn)),
-                        TokenType::GreaterEqual => Ok(Types::Boolean(ln >= rn)),
-                        TokenType::Less => Ok(Types::Boolean(ln < rn)),
-                        TokenType::LessEqual => Ok(Types::Boolean(ln <= rn)),
-                        TokenType::EqualEqual => Ok(Types::Boolean(ln == rn)),
-                        TokenType::BangEqual => Ok(Types::Boolean(ln != rn)),
-                        _ => Err(Error::RunTime {
-                            token: t.clone(),
-                            message: "Operands must be two numbers to compare".to_string(),
-                        }),
-                    },
-                    //For Type Nil
-                    (Types::Nil, t, Types::Nil) => match t.t_type {
-                        TokenType::Equal => Ok(Types::Boolean(true)),
-                        TokenType::BangEqual => Ok(Types::Boolean(false)),
-                        _ => Err(Error::RunTime {
-                            token: t.clone(),
-                            message: "Operands must be nil ".to_string(),
-                        }),
-                    },
-                    //For Type boolean
-                    (Types::Boolean(lb), t, Types::Boolean(rb)) => match t.t_type {
-                        TokenType::Equal => Ok(Types::Boolean(lb == rb)),
-                        TokenType::BangEqual => Ok(Types::Boolean(lb != rb)),
-                        _ => Err(Error::RunTime {
-                            token: t.clone(),
-                            message: "Operands must be boolean".to_string(),
-                        }),
-                    },
-                    _ => Err(Error::RunTime {
-                        token: op.clone(),
-                        message: "Idk what to print".to_string(),
-                    }),
-                };
+                let value = binary(left_expr, op, right_expr)?;
+                self.allocate(heap::shallow_size(&value), op)?;
+                Ok(value)
+            }
+            &Expr::Get {
+                ref object,
+                ref name,
+                ..
+            } => {
+                let object = self.visit_expression(object)?;
+                self.get_property(object, name)
+            }
+            Expr::OptionalChain { expr } => Ok(self.chain(expr)?.unwrap_or(Types::Nil)),
+            &Expr::Ternary {
+                ref condition,
+                ref then_branch,
+                ref else_branch,
+            } => {
+                if istruthy!(self.visit_expression(condition)?) {
+                    self.visit_expression(then_branch)
+                } else {
+                    self.visit_expression(else_branch)
+                }
             }
             //For Grouping Expression
             &Expr::Grouping { ref expr } => self.visit_expression(expr),
             //For Literal returns a return the type
             &Expr::Literal { ref token } => match token.t_type.clone() {
                 TokenType::Number(i) => Ok(Types::Number(i)),
+                TokenType::Int(i) => Ok(Types::Int(i)),
                 TokenType::True => Ok(Types::Boolean(true)),
                 TokenType::False => Ok(Types::Boolean(false)),
                 TokenType::Nil => Ok(Types::Nil),
@@ -310,7 +1124,11 @@ impl Visitor for Interpreter {
                 ref right,
             } => {
                 let left_result = self.visit_expression(left)?;
-                if op.t_type == TokenType::Or {
+                if op.t_type == TokenType::QuestionQuestion {
+                    if !matches!(left_result, Types::Nil) {
+                        return Ok(left_result);
+                    }
+                } else if op.t_type == TokenType::Or {
                     if istruthy!(left_result) {
                         return Ok(left_result);
                     }
@@ -328,12 +1146,15 @@ impl Visitor for Interpreter {
                 match (right, op.t_type.clone()) {
                     //returns negative number
                     (Types::Number(n), TokenType::Minus) => Ok(Types::Number(-n)),
-                    // returns type boolean if Boolean is false
-                    (Types::Nil, TokenType::Bang) | (Types::Boolean(false), TokenType::Bang) => {
-                        Ok(Types::Boolean(false))
+                    (Types::Int(n), TokenType::Tilde) => Ok(Types::Int(!n)),
+                    (Types::Int(n), TokenType::Minus) => {
+                        n.checked_neg().map(Types::Int).ok_or_else(|| Error::RunTime {
+                            token: op.clone(),
+                            message: "Integer overflow.".to_string(),
+                        })
                     }
-                    // return false for any value idk whether it does for Zero lol
-                    (_, TokenType::Bang) => Ok(Types::Boolean(false)),
+                    // nil and false are the only falsey values
+                    (value, TokenType::Bang) => Ok(Types::Boolean(!istruthy!(value))),
                     _ => Err(Error::RunTime {
                         token: op.clone(),
                         message: "Invalid unary Expression".to_string(),
@@ -346,33 +1167,8 @@ impl Visitor for Interpreter {
                 ref arguments,
                 ..
             } => {
-                let callee_value = self.visit_expression(callee)?;
-                let arguments: Result<Vec<Types>, Error> = arguments
-                    .into_iter()
-                    .map(|e| self.visit_expression(e))
-                    .collect();
-                let args = arguments?;
-
-                if let Types::Callable(function) = callee_value {
-                    let args_len = args.len();
-                    if args_len != function.arity() {
-                        Err(Error::RunTime {
-                            token: paren.clone(),
-                            message: format!(
-                                "Expected {} arguments but got {}.",
-                                function.arity(),
-                                args_len
-                            ),
-                        })
-                    } else {
-                        function.call(self, &args)
-                    }
-                } else {
-                    Err(Error::RunTime {
-                        token: paren.clone(),
-                        message: "Can only call functions and classes.".to_string(),
-                    })
-                }
+                let callee = self.visit_expression(callee)?;
+                self.call_value(callee, paren, arguments)
             }
             &Expr::Variable { ref name, .. } => {
                 self.environment.get(name);
@@ -387,23 +1183,636 @@ impl Visitor for Interpreter {
 #[derive(Debug, Clone)]
 pub enum Types {
     Number(f64),
+    Int(i64),
     ReturnString(String),
     Boolean(bool),
     Nil,
     Callable(Rc<Box<dyn Callable>>),
     Class(Rc<RefCell<Class>>),
+    Module(Rc<Module>),
+    List(Rc<RefCell<Vec<Types>>>),
+    Map(Rc<RefCell<Map>>),
+    Error(Rc<ErrorValue>),
+}
+
+impl Types {
+    //Lox equality: any two values can be compared and values of different types are
+    //never equal. Ints and floats are both Lox numbers, so this is intended to compare them
+    //by value: `1 == 1.0` holds and the two are the same map key.
+    //Functions and classes are only equal to themselves, lists and maps compare item by item
+    pub fn equals(&self, other: &Types) -> bool {
+        self.equals_within(other, &mut Vec::new())
+    }
+
+    //`compared` holds the pairs of lists and maps being compared further up, a pair met again
+    //is a cycle and counts as equal so the rest of the two values decides
+    pub(crate) fn equals_within(&self, other: &Types, compared: &mut Vec<(*const (), *const ())>) -> bool {
+        match (self, other) {
+            (Types::Nil, Types::Nil) => true,
+            (Types::Boolean(a), Types::Boolean(b)) => a == b,
+            (Types::Number(a), Types::Number(b)) => a == b,
+            (Types::Int(a), Types::Int(b)) => a == b,
+            (Types::Int(i), Types::Number(n)) | (Types::Number(n), Types::Int(i)) => {
+                n.fract() == 0.0 && n.abs() < 9.2e18 && *n as i64 == *i
+            }
+            (Types::ReturnString(a), Types::ReturnString(b)) => a == b,
+            (Types::Callable(a), Types::Callable(b)) => Rc::ptr_eq(a, b),
+            (Types::Class(a), Types::Class(b)) => Rc::ptr_eq(a, b),
+            (Types::Module(a), Types::Module(b)) => Rc::ptr_eq(a, b),
+            (Types::Error(a), Types::Error(b)) => Rc::ptr_eq(a, b),
+            (Types::List(a), Types::List(b)) => {
+                let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+                if Rc::ptr_eq(a, b) || compared.contains(&pair) {
+                    return true;
+                }
+                let (a, b) = (a.borrow(), b.borrow());
+                compared.push(pair);
+                let equal = a.len() == b.len()
+                    && a.iter().zip(b.iter()).all(|(x, y)| x.equals_within(y, compared));
+                compared.pop();
+                equal
+            }
+            (Types::Map(a), Types::Map(b)) => {
+                let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+                if Rc::ptr_eq(a, b) || compared.contains(&pair) {
+                    return true;
+                }
+                compared.push(pair);
+                let equal = a.borrow().equals_within(&b.borrow(), compared);
+                compared.pop();
+                equal
+            }
+            _ => false,
+        }
+    }
+}
+
+// floats always show a fraction so they can be told apart from ints
+pub fn format_number(n: f64) -> String {
+    if n.is_finite() && n.fract() == 0.0 && n.abs() < 1e16 {
+        format!("{:.1}", n)
+    } else {
+        n.to_string()
+    }
+}
+
+//applies a binary operator to two already evaluated operands
+fn binary(left: Types, op: &Token, right: Types) -> Result<Types, Error> {
+    match op.t_type {
+        TokenType::EqualEqual => return Ok(Types::Boolean(left.equals(&right))),
+        TokenType::BangEqual => return Ok(Types::Boolean(!left.equals(&right))),
+        _ => {}
+    }
+    match (left, op.clone(), right) {
+        // For Strings
+        (Types::ReturnString(ls), t, Types::ReturnString(rs)) => match t.t_type.clone()
+        {
+            TokenType::Plus => {
+                Ok(Types::ReturnString(String::from(format!("{}{}", ls, rs))))
+            }
+            _ => Err(Error::RunTime {
+                token: t.clone(),
+                message: "Operands must be two numbers or two strings".to_string(),
+            }),
+        },
+        //For number basic operation and comparison
+        (Types::Number(ln), t, Types::Number(rn)) => number_binary(ln, &t, rn),
+        (Types::Int(ln), t, Types::Int(rn)) => int_binary(ln, &t, rn),
+        // an int meeting a float is promoted to a float
+        (Types::Int(ln), t, Types::Number(rn)) => number_binary(ln as f64, &t, rn),
+        (Types::Number(ln), t, Types::Int(rn)) => number_binary(ln, &t, rn as f64),
+        (_, t, _) => Err(Error::RunTime {
+            token: t,
+            message: "Operands must be two numbers or two strings".to_string(),
+        }),
+    }
+}
+
+// the plain operator a compound assignment applies, `+=` applies `+`
+fn compound_operator(t_type: &TokenType) -> TokenType {
+    match t_type {
+        TokenType::PlusEqual => TokenType::Plus,
+        TokenType::MinusEqual => TokenType::Minus,
+        TokenType::StarEqual => TokenType::Star,
+        TokenType::SlashEqual => TokenType::Slash,
+        TokenType::PercentEqual => TokenType::Percent,
+        other => other.clone(),
+    }
+}
+
+fn number_binary(ln: f64, t: &Token, rn: f64) -> Result<Types, Error> {
+    match t.t_type {
+        TokenType::Plus => Ok(Types::Number(ln + rn)),
+        TokenType::Minus => Ok(Types::Number(ln - rn)),
+        TokenType::Star => Ok(Types::Number(ln * rn)),
+        TokenType::Slash | TokenType::Percent if rn == 0.0 => Err(Error::RunTime {
+            token: t.clone(),
+            message: "Division by zero.".to_string(),
+        }),
+        TokenType::Slash => Ok(Types::Number(ln / rn)),
+        TokenType::Percent => Ok(Types::Number(ln % rn)),
+        TokenType::StarStar => Ok(Types::Number(ln.powf(rn))),
+        TokenType::Ampersand
+        | TokenType::Pipe
+        | TokenType::Caret
+        | TokenType::LessLess
+        | TokenType::GreaterGreater => Err(Error::RunTime {
+            token: t.clone(),
+            message: "Operands of a bitwise operator must be integers.".to_string(),
+        }),
+        TokenType::Greater => Ok(Types::Boolean(ln > rn)),
+        TokenType::GreaterEqual => Ok(Types::Boolean(ln >= rn)),
+        TokenType::Less => Ok(Types::Boolean(ln < rn)),
+        TokenType::LessEqual => Ok(Types::Boolean(ln <= rn)),
+        _ => Err(Error::RunTime {
+            token: t.clone(),
+            message: "Operands must be two numbers to compare".to_string(),
+        }),
+    }
+}
+
+// integer arithmetic is checked, `/` truncates towards zero like `%` does
+fn int_binary(ln: i64, t: &Token, rn: i64) -> Result<Types, Error> {
+    let overflow = || Error::RunTime {
+        token: t.clone(),
+        message: "Integer overflow.".to_string(),
+    };
+    match t.t_type {
+        TokenType::Plus => ln.checked_add(rn).map(Types::Int).ok_or_else(overflow),
+        TokenType::Minus => ln.checked_sub(rn).map(Types::Int).ok_or_else(overflow),
+        TokenType::Star => ln.checked_mul(rn).map(Types::Int).ok_or_else(overflow),
+        TokenType::Slash | TokenType::Percent if rn == 0 => Err(Error::RunTime {
+            token: t.clone(),
+            message: "Division by zero.".to_string(),
+        }),
+        TokenType::Slash => ln.checked_div(rn).map(Types::Int).ok_or_else(overflow),
+        TokenType::Percent => ln.checked_rem(rn).map(Types::Int).ok_or_else(overflow),
+        // a negative exponent can't stay an integer
+        TokenType::StarStar if rn < 0 => Ok(Types::Number((ln as f64).powf(rn as f64))),
+        TokenType::StarStar => u32::try_from(rn)
+            .ok()
+            .and_then(|exp| ln.checked_pow(exp))
+            .map(Types::Int)
+            .ok_or_else(overflow),
+        TokenType::Ampersand => Ok(Types::Int(ln & rn)),
+        TokenType::Pipe => Ok(Types::Int(ln | rn)),
+        TokenType::Caret => Ok(Types::Int(ln ^ rn)),
+        TokenType::LessLess | TokenType::GreaterGreater if !(0..64).contains(&rn) => {
+            Err(Error::RunTime {
+                token: t.clone(),
+                message: "Shift amount must be between 0 and 63.".to_string(),
+            })
+        }
+        TokenType::LessLess => Ok(Types::Int(ln << rn)),
+        TokenType::GreaterGreater => Ok(Types::Int(ln >> rn)),
+        TokenType::Greater => Ok(Types::Boolean(ln > rn)),
+        TokenType::GreaterEqual => Ok(Types::Boolean(ln >= rn)),
+        TokenType::Less => Ok(Types::Boolean(ln < rn)),
+        TokenType::LessEqual => Ok(Types::Boolean(ln <= rn)),
+        _ => Err(Error::RunTime {
+            token: t.clone(),
+            message: "Operands must be two numbers to compare".to_string(),
+        }),
+    }
 }
 
 // implements Display Trait to print
 impl Display for Types {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        self.fmt_within(f, &mut Vec::new())
+    }
+}
+
+impl Types {
+    //`open` holds the lists and maps being written further up, one that contains itself shows as `[...]`
+    pub(crate) fn fmt_within(&self, f: &mut Formatter, open: &mut Vec<*const ()>) -> FmtResult {
         match self {
             &Types::Boolean(b) => write!(f, "{}", b),
             &Types::Nil => write!(f, "nil"),
-            &Types::Number(n) => write!(f, "{}", n),
+            &Types::Number(n) => write!(f, "{}", format_number(n)),
+            &Types::Int(n) => write!(f, "{}", n),
             &Types::ReturnString(ref s) => write!(f, "\"{}\"", s.to_string()),
             &Types::Callable(ref call) => write!(f, "{}", call),
             &Types::Class(ref class) => write!(f, "{:?}", class),
+            &Types::Module(ref module) => write!(f, "{}", module),
+            Types::List(items) => {
+                let container = Rc::as_ptr(items) as *const ();
+                if open.contains(&container) {
+                    return write!(f, "[...]");
+                }
+                open.push(container);
+                write!(f, "[")?;
+                for (i, item) in items.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.fmt_within(f, open)?;
+                }
+                open.pop();
+                write!(f, "]")
+            }
+            Types::Map(map) => {
+                let container = Rc::as_ptr(map) as *const ();
+                if open.contains(&container) {
+                    return write!(f, "{{...}}");
+                }
+                open.push(container);
+                map.borrow().fmt_within(f, open)?;
+                open.pop();
+                Ok(())
+            }
+            &Types::Error(ref e) => write!(f, "<error {}>", e.message),
+        }
+    }
+}
+
+//runs a whole script and returns everything it printed, for tests across the crate
+#[cfg(test)]
+pub fn run_captured(source: &str) -> Result<String, Error> {
+    let tokens = Scanner::new(source.as_bytes()).scan_tokens();
+    let statements = Parser::new(tokens).parse()?;
+    let output = CapturedOutput::default();
+    let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+    Resolver::new(&mut interpreter).resolve_stmts(&statements);
+    interpreter.interpret(statements)?;
+    Ok(output.contents())
+}
+
+#[cfg(test)]
+mod eval_tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    // runs `var result = <expr>;` and hands back the value of result
+    fn eval(expr: &str) -> Result<Types, Error> {
+        let source = format!("var result = {};", expr);
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens();
+        let statements = Parser::new(tokens).parse()?;
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(statements)?;
+        interpreter.globals.get(&Token {
+            t_type: TokenType::Identifier("result".to_string()),
+            lexeme: "result".to_string(),
+            line: 0,
+            column: 0,
+        })
+    }
+
+    #[test]
+    fn test_int_arithmetic_keeps_precision() {
+        assert!(matches!(eval("9007199254740993 + 0"), Ok(Types::Int(9007199254740993))));
+        assert!(matches!(eval("7 / 2"), Ok(Types::Int(3))));
+        assert!(matches!(eval("-7 % 3"), Ok(Types::Int(-1))));
+    }
+
+    #[test]
+    fn test_int_promotes_to_float() {
+        assert!(matches!(eval("7 / 2.0"), Ok(Types::Number(n)) if n == 3.5));
+        assert!(matches!(eval("1 + 0.5"), Ok(Types::Number(n)) if n == 1.5));
+        assert!(matches!(eval("2 < 2.5"), Ok(Types::Boolean(true))));
+    }
+
+    #[test]
+    fn test_int_overflow_and_division_by_zero() {
+        for expr in ["9223372036854775807 + 1", "-9223372036854775807 - 2", "1 / 0", "1 % 0"] {
+            assert!(matches!(eval(expr), Err(Error::RunTime { .. })), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_power_is_right_associative() {
+        assert!(matches!(eval("2 ** 3 ** 2"), Ok(Types::Int(512))));
+        assert!(matches!(eval("-2 ** 2"), Ok(Types::Int(-4))));
+        assert!(matches!(eval("2 ** -1"), Ok(Types::Number(n)) if n == 0.5));
+        assert!(matches!(eval("2 ** 64"), Err(Error::RunTime { .. })));
+    }
+
+    #[test]
+    fn test_bitwise_operators() {
+        assert!(matches!(eval("6 & 3 | 8 ^ 1"), Ok(Types::Int(11))));
+        assert!(matches!(eval("~0"), Ok(Types::Int(-1))));
+        assert!(matches!(eval("1 << 4 >> 2"), Ok(Types::Int(4))));
+        assert!(matches!(eval("1 + 1 << 2"), Ok(Types::Int(8))));
+        assert!(matches!(eval("1.5 & 1"), Err(Error::RunTime { .. })));
+        assert!(matches!(eval("1 << 64"), Err(Error::RunTime { .. })));
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let source = "var a = 10; a += 5; a -= 1; a *= 2; a /= 4; a %= 4; var b = (a += 1);";
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens();
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(Parser::new(tokens).parse().unwrap()).unwrap();
+        let b = Token {
+            t_type: TokenType::Identifier("b".to_string()),
+            lexeme: "b".to_string(),
+            line: 0,
+            column: 0,
+        };
+        assert!(matches!(interpreter.globals.get(&b), Ok(Types::Int(4))));
+    }
+
+    #[test]
+    fn test_ternary_and_coalescing_short_circuit() {
+        assert!(matches!(eval("true ? 1 : 1 / 0"), Ok(Types::Int(1))));
+        assert!(matches!(eval("nil ? 1 / 0 : 2"), Ok(Types::Int(2))));
+        assert!(matches!(eval("false ? 1 : nil ? 2 : 3"), Ok(Types::Int(3))));
+        assert!(matches!(eval("nil ?? 3"), Ok(Types::Int(3))));
+        assert!(matches!(eval("false ?? 1 / 0"), Ok(Types::Boolean(false))));
+        assert!(matches!(eval("nil ?? nil ?? 4"), Ok(Types::Int(4))));
+    }
+
+    #[test]
+    fn test_optional_chaining_on_nil() {
+        assert!(matches!(eval("nil?.name"), Ok(Types::Nil)));
+        assert!(matches!(eval("nil?.name ?? 5"), Ok(Types::Int(5))));
+        assert!(matches!(eval("nil.name"), Err(Error::RunTime { .. })));
+        // the rest of the chain is skipped, not just the next access
+        assert!(matches!(eval("nil?.name.first"), Ok(Types::Nil)));
+        assert!(matches!(eval("nil?.greet()"), Ok(Types::Nil)));
+        assert!(matches!(eval("nil?.greet().name(1 / 0)"), Ok(Types::Nil)));
+        // a grouping ends the chain
+        assert!(matches!(eval("(nil?.name).first"), Err(Error::RunTime { .. })));
+        assert!(matches!(eval("clock?.name.first"), Err(Error::RunTime { .. })));
+    }
+
+    #[test]
+    fn test_equality_across_types() {
+        for (expr, expected) in [
+            ("nil == nil", true),
+            ("nil == false", false),
+            ("true != false", true),
+            ("1 == \"1\"", false),
+            ("\"a\" == \"a\"", true),
+            ("1 == 1.0", true),
+            ("9007199254740993 == 9007199254740992.0", false),
+            ("nil != 0", true),
+            ("clock == clock", true),
+            ("clock == assert", false),
+            ("assert(1, 1.0)", true),
+            ("assert(\"1\", 1)", false),
+        ] {
+            assert!(matches!(eval(expr), Ok(Types::Boolean(b)) if b == expected), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_functions_compare_by_identity() {
+        let source = "fun f() {} fun g() {} var h = f; var same = h == f; var other = f == g;";
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens();
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(Parser::new(tokens).parse().unwrap()).unwrap();
+        let get = |name: &str| {
+            interpreter.globals.get(&Token {
+                t_type: TokenType::Identifier(name.to_string()),
+                lexeme: name.to_string(),
+                line: 0,
+                column: 0,
+            })
+        };
+        assert!(matches!(get("same"), Ok(Types::Boolean(true))));
+        assert!(matches!(get("other"), Ok(Types::Boolean(false))));
+    }
+
+    #[test]
+    fn test_ordering_needs_numbers() {
+        assert!(matches!(eval("1 < \"2\""), Err(Error::RunTime { .. })));
+        assert!(matches!(eval("nil + 1"), Err(Error::RunTime { .. })));
+    }
+
+    #[test]
+    fn test_conversion_natives() {
+        assert!(matches!(eval("int(3.9)"), Ok(Types::Int(3))));
+        assert!(matches!(eval("int(\"42\")"), Ok(Types::Int(42))));
+        assert!(matches!(eval("float(2)"), Ok(Types::Number(n)) if n == 2.0));
+        assert!(matches!(eval("int(\"4x\")"), Err(Error::RunTime { .. })));
+        assert_eq!(Interpreter::new().stringify(Types::Number(3.0)), "3.0");
+    }
+
+    #[test]
+    fn test_import_cycle_is_reported() {
+        let dir = std::env::temp_dir().join(format!("rlox-import-cycle-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.lox"), "import \"b.lox\";").unwrap();
+        fs::write(dir.join("b.lox"), "import \"a.lox\";").unwrap();
+
+        let tokens = Scanner::new(b"import \"b.lox\";").scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.capabilities.imports = true;
+        interpreter.set_script_path(&dir.join("a.lox"));
+        let result = interpreter.interpret(statements);
+        fs::remove_dir_all(&dir).unwrap();
+
+        match result {
+            Err(Error::RunTime { message, .. }) => {
+                assert_eq!(message, "Import cycle: a.lox -> b.lox -> a.lox.")
+            }
+            other => panic!("expected an import cycle error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_imports_are_disabled_by_default() {
+        match run_captured("import \"math.lox\";") {
+            Err(Error::RunTime { message, .. }) => {
+                assert_eq!(message, "Could not import 'math.lox': loading modules is disabled.")
+            }
+            other => panic!("expected imports to be disabled, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_print_writes_to_the_output_sink() {
+        let output = run_captured("print 1 + 2;\nprint \"a\" + \"b\";\nprint nil;").unwrap();
+        assert_eq!(output, "3\nab\nnil\n");
+    }
+
+    #[test]
+    fn test_captured_output_is_shared_between_clones() {
+        let output = CapturedOutput::default();
+        let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+        writeln!(interpreter.output(), "from a native").unwrap();
+        assert_eq!(output.contents(), "from a native\n");
+    }
+
+    fn run_limited(source: &str, limits: Limits) -> Result<(), Error> {
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens();
+        let statements = Parser::new(tokens).parse()?;
+        let mut interpreter = Interpreter::with_output(Box::new(CapturedOutput::default()));
+        Resolver::new(&mut interpreter).resolve_stmts(&statements);
+        interpreter.set_limits(limits);
+        interpreter.interpret(statements)
+    }
+
+    #[test]
+    fn test_step_limit_stops_infinite_loops() {
+        let limits = Limits {
+            max_steps: Some(1000),
+            ..Limits::default()
+        };
+        match run_limited("while (true) {}", limits) {
+            Err(Error::Limit { message }) => assert_eq!(message, "Step limit of 1000 exceeded."),
+            other => panic!("expected the step limit, got {:?}", other),
+        }
+        assert!(run_limited("var i = 0; while (i < 10) i = i + 1;", limits).is_ok());
+    }
+
+    #[test]
+    fn test_limits_cannot_be_caught() {
+        let limits = Limits {
+            max_steps: Some(100),
+            ..Limits::default()
+        };
+        let source = "try { while (true) {} } catch (e) { print e; } finally { print 1; }";
+        assert!(std::matches!(run_limited(source, limits), Err(Error::Limit { .. })));
+    }
+
+    #[test]
+    fn test_timeout_stops_long_running_scripts() {
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(50)),
+            ..Limits::default()
+        };
+        let started = Instant::now();
+        match run_limited("for (;;) {}", limits) {
+            Err(Error::Limit { message }) => assert_eq!(message, "Timeout of 50ms exceeded."),
+            other => panic!("expected the timeout, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_heap_limit_is_a_catchable_error() {
+        let limits = Limits {
+            max_heap: Some(64 * 1024),
+            ..Limits::default()
+        };
+        let source = "
+            var caught = nil;
+            try {
+                var s = \"x\";
+                while (true) s = s + s;
+            } catch (e) {
+                caught = e.message;
+            }
+            // the doubled string was dropped with its block, there is room again
+            var t = \"y\";
+            for (var i = 0; i < 1000; i = i + 1) t = t + \"y\";
+            if (caught != nil) exit(len(t));";
+        match run_limited(source, limits) {
+            Err(Error::Exit { code }) => assert_eq!(code, 1001),
+            other => panic!("expected the heap limit to be caught, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_heap_limit_message() {
+        let limits = Limits {
+            max_heap: Some(4096),
+            ..Limits::default()
+        };
+        match run_limited("var s = repeat(\"ab\", 4096);", limits) {
+            Err(Error::RunTime { message, .. }) => {
+                assert_eq!(message, "Out of memory: heap limit of 4096 bytes exceeded.")
+            }
+            other => panic!("expected out of memory, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_heap_limit_sees_items_added_to_collections() {
+        let limits = Limits {
+            max_heap: Some(64 * 1024),
+            ..Limits::default()
+        };
+        for source in [
+            "var a = list(); while (true) push(a, 1);",
+            "var m = map(); var i = 0; while (true) { set(m, str(i), i); i = i + 1; }",
+        ] {
+            match run_limited(source, limits) {
+                Err(Error::RunTime { message, .. }) => {
+                    assert_eq!(message, "Out of memory: heap limit of 65536 bytes exceeded.")
+                }
+                other => panic!("expected out of memory from {}, got {:?}", source, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_heap_limit_applies_before_repeat_builds_its_string() {
+        let limits = Limits {
+            max_heap: Some(4096),
+            ..Limits::default()
+        };
+        match run_limited("var s = repeat(\"ab\", 1000000000000);", limits) {
+            Err(Error::RunTime { message, .. }) => {
+                assert_eq!(message, "Out of memory: heap limit of 4096 bytes exceeded.")
+            }
+            other => panic!("expected out of memory, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_natives_only_charge_values_they_build() {
+        let mut interpreter = Interpreter::with_output(Box::new(CapturedOutput::default()));
+        let mut heap_after = |source: &str| {
+            let tokens = Scanner::new(source.as_bytes()).scan_tokens();
+            interpreter.interpret(Parser::new(tokens).parse().unwrap()).unwrap();
+            interpreter.heap_bytes
+        };
+        let before = heap_after("var l = list(); push(l, \"some text\"); var m = map(); set(m, 1, l);");
+        // handing back what the script already holds is free
+        assert_eq!(heap_after("get(l, 0); get(m, 1); min(1, 2); len(l);"), before);
+        // a new string or list is charged
+        assert!(heap_after("upper(get(l, 0));") > before);
+        let before = heap_after("");
+        assert_eq!(heap_after("values(m);"), before + heap::SLOT_SIZE);
+    }
+
+    #[test]
+    fn test_call_depth_is_a_stack_overflow() {
+        //test threads have a small native stack, so stay shallow
+        let limits = Limits {
+            max_call_depth: Some(20),
+            ..Limits::default()
+        };
+        match run_limited("fun f() { return f(); } f();", limits) {
+            Err(Error::RunTime { message, .. }) => assert_eq!(message, "Stack overflow."),
+            other => panic!("expected a stack overflow, got {:?}", other),
+        }
+        let source = "fun f(n) { if (n > 0) f(n - 1); } f(19);";
+        assert!(run_limited(source, limits).is_ok());
+    }
+
+    #[test]
+    fn test_assert_raises_counts_towards_the_call_depth() {
+        let limits = Limits {
+            max_call_depth: Some(1),
+            ..Limits::default()
+        };
+        // `g` would be the second call in progress, so it overflows instead of returning
+        let source = "fun g() { return 1; } assert_raises(g);";
+        assert!(run_limited(source, limits).is_ok());
+    }
+
+    #[test]
+    fn test_assert_raises_lets_limits_through() {
+        let limits = Limits {
+            max_steps: Some(100),
+            ..Limits::default()
+        };
+        let source = "fun spin() { while (true) {} } assert_raises(spin);";
+        assert!(std::matches!(run_limited(source, limits), Err(Error::Limit { .. })));
+    }
+
+    #[test]
+    fn test_exit_unwinds_through_function_calls() {
+        let source = "fun quit() { exit(4); }\ntry { quit(); } catch (e) { print e; }";
+        match run_captured(source) {
+            Err(Error::Exit { code }) => assert_eq!(code, 4),
+            other => panic!("expected exit(4), got {:?}", other),
         }
     }
 }
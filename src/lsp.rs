@@ -0,0 +1,803 @@
+//Editor support, `rlox lsp` speaks the Language Server Protocol over stdio.
+//Every request re-scans, re-parses and re-resolves the document, Lox files are small.
+use crate::error::{self, Diagnostic};
+use crate::interpreter::{Interpreter, Types};
+use crate::parser::Parser;
+use crate::protocol::{self, field, int_field, list, object, string, text_field};
+use crate::resolver::{Binding, BindingKind, Resolver};
+use crate::scanner::Scanner;
+use crate::token::{Token, TokenType};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+
+//numbers the protocol gives to severities, symbol and completion kinds
+const SEVERITY_ERROR: i64 = 1;
+const SYMBOL_CLASS: i64 = 5;
+const SYMBOL_FUNCTION: i64 = 12;
+const COMPLETION_FUNCTION: i64 = 3;
+const COMPLETION_VARIABLE: i64 = 6;
+const COMPLETION_CLASS: i64 = 7;
+const COMPLETION_MODULE: i64 = 9;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+//line and column of a token, both 1-based like the scanner's
+type Position = (u32, u32);
+
+fn position(token: &Token) -> Position {
+    (token.line, token.column)
+}
+
+//the protocol counts characters in UTF-16 code units, the scanner's columns count bytes
+fn utf16_len(text: &str) -> i64 {
+    text.chars().map(|c| c.len_utf16() as i64).sum()
+}
+
+//the 1-based byte column of the character `character` UTF-16 units into `line`
+fn byte_column(line: &str, character: u32) -> u32 {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= character {
+            return i as u32 + 1;
+        }
+        units += c.len_utf16() as u32;
+    }
+    line.len() as u32 + 1
+}
+
+//the protocol counts lines and characters from 0
+fn lsp_position(line: i64, character: i64) -> Types {
+    object(vec![
+        ("line", Types::Int(line)),
+        ("character", Types::Int(character)),
+    ])
+}
+
+//a name every script can use without declaring it
+struct Builtin {
+    name: String,
+    arity: usize,
+    optional: usize,
+    doc: Option<String>,
+}
+
+fn builtins() -> Vec<Builtin> {
+    let interpreter = Interpreter::with_output(Box::new(io::sink()));
+    let mut builtins = Vec::new();
+    interpreter.environment().for_each(|name, value| {
+        if let Types::Callable(function) = value {
+            builtins.push(Builtin {
+                name: name.to_string(),
+                arity: function.arity(),
+                optional: function.optional(),
+                doc: function.doc(),
+            });
+        }
+    });
+    builtins.sort_by(|a, b| a.name.cmp(&b.name));
+    builtins
+}
+
+fn arguments(arity: usize, optional: usize) -> String {
+    match (arity - optional, arity) {
+        (1, 1) => "Takes 1 argument.".to_string(),
+        (min, max) if min == max => format!("Takes {} arguments.", max),
+        (min, max) => format!("Takes {} to {} arguments.", min, max),
+    }
+}
+
+//what the server knows about one version of a document
+struct Analysis {
+    tokens: Vec<Token>,
+    diagnostics: Vec<Diagnostic>,
+    bindings: Vec<Binding>,
+    // the innermost `{ }` block each token is in, and whether it sits in parentheses there
+    block_of: Vec<Option<usize>>,
+    in_parens: Vec<bool>,
+    // where each block opens and closes, a block left open runs to the end of the document
+    blocks: Vec<(Position, Position)>,
+    // the document's text, to turn byte columns into the protocol's UTF-16 ones and back
+    lines: Vec<String>,
+}
+
+impl Analysis {
+    fn new(source: &str) -> Self {
+        // errors are reported as diagnostics, nothing may be printed over the protocol
+        let ((tokens, bindings), diagnostics) = error::capture(|| {
+            let tokens = Scanner::new(source.as_bytes()).scan_tokens();
+            let statements = Parser::new(tokens.clone()).parse().unwrap_or_default();
+            let tokens: Vec<Token> = tokens
+                .into_iter()
+                .filter(|token| !std::matches!(token.t_type, TokenType::DocComment(_)))
+                .collect();
+            let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+            let mut resolver = Resolver::new(&mut interpreter);
+            resolver.resolve_stmts(&statements);
+            (tokens, resolver.into_bindings())
+        });
+
+        let mut block_of = Vec::with_capacity(tokens.len());
+        let mut in_parens = Vec::with_capacity(tokens.len());
+        let mut blocks = Vec::new();
+        // open blocks with the parentheses open in each, the document itself is the first
+        let mut open: Vec<(Option<usize>, usize)> = vec![(None, 0)];
+        let end = tokens.last().map(position).unwrap_or((1, 1));
+        for token in &tokens {
+            let (block, parens) = open.last_mut().expect("the document is never closed");
+            block_of.push(*block);
+            in_parens.push(*parens > 0);
+            match token.t_type {
+                TokenType::LeftParen => *parens += 1,
+                TokenType::RightParen => *parens = parens.saturating_sub(1),
+                TokenType::LeftBrace => {
+                    blocks.push((position(token), end));
+                    open.push((Some(blocks.len() - 1), 0));
+                }
+                TokenType::RightBrace if open.len() > 1 => {
+                    if let Some((Some(block), _)) = open.pop() {
+                        blocks[block].1 = position(token);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self {
+            tokens,
+            diagnostics,
+            bindings,
+            block_of,
+            in_parens,
+            blocks,
+            lines: source.lines().map(String::from).collect(),
+        }
+    }
+
+    fn line(&self, line: u32) -> &str {
+        let index = line.saturating_sub(1) as usize;
+        self.lines.get(index).map_or("", String::as_str)
+    }
+
+    //the 0-based UTF-16 character of a 1-based byte column
+    fn character(&self, line: u32, column: u32) -> i64 {
+        let text = self.line(line);
+        let mut end = (column.saturating_sub(1) as usize).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        utf16_len(&text[..end])
+    }
+
+    fn range(&self, token: &Token) -> Types {
+        let start = self.character(token.line, token.column);
+        self.span_from(token.line, start, utf16_len(&token.lexeme))
+    }
+
+    //`length` characters from a byte column, as diagnostics give them
+    fn span(&self, line: u32, column: u32, length: usize) -> Types {
+        let start = self.character(line, column);
+        let text = self.line(line);
+        let rest = text.get(text.len().min(column.saturating_sub(1) as usize)..).unwrap_or("");
+        let taken: Vec<char> = rest.chars().take(length).collect();
+        // past the end of the line each character counts as one unit
+        let units = taken.iter().map(|c| c.len_utf16() as i64).sum::<i64>()
+            + (length - taken.len()) as i64;
+        self.span_from(line, start, units)
+    }
+
+    fn span_from(&self, line: u32, start: i64, length: i64) -> Types {
+        let line = line.saturating_sub(1) as i64;
+        object(vec![
+            ("start", lsp_position(line, start)),
+            ("end", lsp_position(line, start + length)),
+        ])
+    }
+
+    fn location(&self, uri: &str, name: &Token) -> Types {
+        object(vec![("uri", string(uri)), ("range", self.range(name))])
+    }
+
+    //a protocol position as the scanner's line and byte column, `None` when it is negative
+    fn position_at(&self, line: i64, character: i64) -> Option<Position> {
+        let line = u32::try_from(line).ok()?.checked_add(1)?;
+        let character = u32::try_from(character).ok()?;
+        Some((line, byte_column(self.line(line), character)))
+    }
+
+    //the name under the cursor, the cursor may also sit just after it
+    fn name_at(&self, at: Position) -> Option<&Token> {
+        self.tokens.iter().find(|token| {
+            std::matches!(token.t_type, TokenType::Identifier(_))
+                && token.line == at.0
+                && token.column <= at.1
+                && at.1 <= token.column + token.lexeme.chars().count() as u32
+        })
+    }
+
+    //the binding a name under the cursor declares or uses
+    fn binding_at(&self, at: Position) -> Option<&Binding> {
+        let name = position(self.name_at(at)?);
+        self.bindings.iter().find(|binding| {
+            position(&binding.name) == name
+                || binding.references.iter().any(|use_| position(use_) == name)
+        })
+    }
+
+    //The block a local binding can be used in. Parameters, catch names and loop variables are
+    //declared in parentheses before the block they belong to.
+    fn home(&self, binding: &Binding) -> Option<usize> {
+        let index = self
+            .tokens
+            .iter()
+            .position(|token| position(token) == position(&binding.name))?;
+        let block = self.block_of[index];
+        if !self.in_parens[index] {
+            return block;
+        }
+        self.tokens[index..]
+            .iter()
+            .zip(&self.block_of[index..])
+            .filter(|(token, inside)| token.t_type == TokenType::LeftBrace && **inside == block)
+            .find_map(|(token, _)| self.blocks.iter().position(|b| b.0 == position(token)))
+            .or(block)
+    }
+
+    //top level names can be used anywhere, functions may call ones declared after them
+    fn visible(&self, binding: &Binding, at: Position) -> bool {
+        if binding.depth == 0 {
+            return true;
+        }
+        position(&binding.name) < at
+            && match self.home(binding) {
+                Some(block) => self.blocks[block].0 < at && at <= self.blocks[block].1,
+                None => true,
+            }
+    }
+}
+
+fn signature(binding: &Binding) -> String {
+    let name = &binding.name.lexeme;
+    match &binding.kind {
+        BindingKind::Function { params, .. } => format!("fun {}({})", name, params.join(", ")),
+        BindingKind::Class { .. } => format!("class {}", name),
+        BindingKind::Variable => format!("var {}", name),
+        BindingKind::Parameter => format!("parameter {}", name),
+        BindingKind::Import => format!("import {}", name),
+    }
+}
+
+fn hover_text(binding: &Binding) -> String {
+    let mut text = format!("```lox\n{}\n```", signature(binding));
+    let doc = match &binding.kind {
+        BindingKind::Function { params, doc } => {
+            text.push_str(&format!("\n{}", arguments(params.len(), 0)));
+            doc
+        }
+        BindingKind::Class { doc } => doc,
+        _ => &None,
+    };
+    if let Some(doc) = doc {
+        text.push_str(&format!("\n\n{}", doc));
+    }
+    text
+}
+
+fn completion_kind(binding: &Binding) -> i64 {
+    match binding.kind {
+        BindingKind::Function { .. } => COMPLETION_FUNCTION,
+        BindingKind::Class { .. } => COMPLETION_CLASS,
+        BindingKind::Import => COMPLETION_MODULE,
+        BindingKind::Variable | BindingKind::Parameter => COMPLETION_VARIABLE,
+    }
+}
+
+//the open documents by uri and what the client asked for so far
+struct Server {
+    documents: HashMap<String, String>,
+    builtins: Vec<Builtin>,
+    shutdown: bool,
+}
+
+//Serves one editor until it sends `exit`, returns the status to exit with, 1 when the
+//editor went away without asking for a shutdown first
+pub fn serve<R: BufRead, W: Write>(mut input: R, mut out: W) -> i32 {
+    let mut server = Server {
+        documents: HashMap::new(),
+        builtins: builtins(),
+        shutdown: false,
+    };
+    while let Some(message) = protocol::read(&mut input) {
+        let method = text_field(&message, "method").unwrap_or_default();
+        let params = field(&message, "params").unwrap_or(Types::Nil);
+        if method == "exit" {
+            return if server.shutdown { 0 } else { 1 };
+        }
+        let id = match field(&message, "id") {
+            Some(id) => id,
+            None => {
+                if let Some(uri) = server.notification(&method, &params) {
+                    protocol::write(&mut out, &server.publish_diagnostics(&uri));
+                }
+                continue;
+            }
+        };
+        let mut reply = vec![("jsonrpc", string("2.0")), ("id", id)];
+        match server.request(&method, &params) {
+            Some(result) => reply.push(("result", result)),
+            None => reply.push((
+                "error",
+                object(vec![
+                    ("code", Types::Int(METHOD_NOT_FOUND)),
+                    (
+                        "message",
+                        string(&format!("Unsupported method '{}'.", method)),
+                    ),
+                ]),
+            )),
+        }
+        protocol::write(&mut out, &object(reply));
+    }
+    1
+}
+
+impl Server {
+    //keeps the documents up to date, returns the uri whose diagnostics changed
+    fn notification(&mut self, method: &str, params: &Types) -> Option<String> {
+        let document = field(params, "textDocument")?;
+        let uri = text_field(&document, "uri")?;
+        match method {
+            "textDocument/didOpen" => {
+                let text = text_field(&document, "text").unwrap_or_default();
+                self.documents.insert(uri.clone(), text);
+            }
+            // the server asks for whole documents, the last change holds all of the text
+            "textDocument/didChange" => {
+                let text = match field(params, "contentChanges") {
+                    Some(Types::List(changes)) => changes
+                        .borrow()
+                        .last()
+                        .and_then(|change| text_field(change, "text"))?,
+                    _ => return None,
+                };
+                self.documents.insert(uri.clone(), text);
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+            }
+            _ => return None,
+        }
+        Some(uri)
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Types {
+        let diagnostics = match self.documents.get(uri) {
+            Some(source) => {
+                let analysis = Analysis::new(source);
+                analysis
+                    .diagnostics
+                    .iter()
+                    .map(|diagnostic| {
+                        object(vec![
+                            (
+                                "range",
+                                analysis.span(diagnostic.line, diagnostic.column, diagnostic.length),
+                            ),
+                            ("severity", Types::Int(SEVERITY_ERROR)),
+                            ("source", string("rlox")),
+                            ("message", string(&diagnostic.message)),
+                        ])
+                    })
+                    .collect()
+            }
+            // a closed document's diagnostics are cleared
+            None => Vec::new(),
+        };
+        object(vec![
+            ("jsonrpc", string("2.0")),
+            ("method", string("textDocument/publishDiagnostics")),
+            (
+                "params",
+                object(vec![
+                    ("uri", string(uri)),
+                    ("diagnostics", list(diagnostics)),
+                ]),
+            ),
+        ])
+    }
+
+    //the result of a request, `None` for methods the server does not know
+    fn request(&mut self, method: &str, params: &Types) -> Option<Types> {
+        match method {
+            "initialize" => Some(object(vec![
+                (
+                    "capabilities",
+                    object(vec![
+                        ("textDocumentSync", Types::Int(1)),
+                        ("definitionProvider", Types::Boolean(true)),
+                        ("referencesProvider", Types::Boolean(true)),
+                        ("documentSymbolProvider", Types::Boolean(true)),
+                        ("hoverProvider", Types::Boolean(true)),
+                        ("completionProvider", object(Vec::new())),
+                    ]),
+                ),
+                ("serverInfo", object(vec![("name", string("rlox"))])),
+            ])),
+            "shutdown" => {
+                self.shutdown = true;
+                Some(Types::Nil)
+            }
+            "textDocument/definition" => Some(self.with_document(params, |uri, analysis, at| {
+                let binding = analysis.binding_at(at)?;
+                Some(analysis.location(uri, &binding.name))
+            })),
+            "textDocument/references" => Some(self.with_document(params, |uri, analysis, at| {
+                let binding = analysis.binding_at(at)?;
+                let context = field(params, "context").unwrap_or(Types::Nil);
+                let declaration = std::matches!(
+                    field(&context, "includeDeclaration"),
+                    Some(Types::Boolean(true))
+                );
+                let mut names: Vec<&Token> = binding.references.iter().collect();
+                if declaration {
+                    names.push(&binding.name);
+                }
+                names.sort_by_key(|name| position(name));
+                Some(list(
+                    names.into_iter().map(|name| analysis.location(uri, name)).collect(),
+                ))
+            })),
+            "textDocument/documentSymbol" => {
+                Some(self.with_document(params, |uri, analysis, _| {
+                    let symbols = analysis
+                        .bindings
+                        .iter()
+                        .filter_map(|binding| {
+                            let kind = match binding.kind {
+                                BindingKind::Function { .. } => SYMBOL_FUNCTION,
+                                BindingKind::Class { .. } => SYMBOL_CLASS,
+                                _ => return None,
+                            };
+                            Some(object(vec![
+                                ("name", string(&binding.name.lexeme)),
+                                ("kind", Types::Int(kind)),
+                                ("location", analysis.location(uri, &binding.name)),
+                            ]))
+                        })
+                        .collect();
+                    Some(list(symbols))
+                }))
+            }
+            "textDocument/hover" => Some(self.with_document(params, |_, analysis, at| {
+                let text = match analysis.binding_at(at) {
+                    Some(binding) => hover_text(binding),
+                    None => {
+                        let name = analysis.name_at(at)?;
+                        let builtin = self.builtins.iter().find(|b| b.name == name.lexeme)?;
+                        let mut text = format!(
+                            "```lox\nfun {}\n```\nBuilt in. {}",
+                            builtin.name,
+                            arguments(builtin.arity, builtin.optional)
+                        );
+                        if let Some(doc) = &builtin.doc {
+                            text.push_str(&format!("\n\n{}", doc));
+                        }
+                        text
+                    }
+                };
+                Some(object(vec![(
+                    "contents",
+                    object(vec![("kind", string("markdown")), ("value", string(&text))]),
+                )]))
+            })),
+            "textDocument/completion" => Some(self.with_document(params, |_, analysis, at| {
+                Some(list(self.completions(analysis, at)))
+            })),
+            _ => None,
+        }
+    }
+
+    //runs `answer` on the analysis of the document a request names at the position it names,
+    //`nil` when there is no such document or nothing to answer
+    fn with_document(
+        &self,
+        params: &Types,
+        answer: impl FnOnce(&str, &Analysis, Position) -> Option<Types>,
+    ) -> Types {
+        let uri = field(params, "textDocument").and_then(|document| text_field(&document, "uri"));
+        let (uri, source) = match uri.and_then(|uri| self.documents.get_key_value(&uri)) {
+            Some(document) => document,
+            None => return Types::Nil,
+        };
+        let analysis = Analysis::new(source);
+        let at = match field(params, "position") {
+            Some(position) => {
+                let line = int_field(&position, "line").unwrap_or(0);
+                let character = int_field(&position, "character").unwrap_or(0);
+                match analysis.position_at(line, character) {
+                    Some(at) => at,
+                    None => return Types::Nil,
+                }
+            }
+            None => (0, 0),
+        };
+        answer(uri, &analysis, at).unwrap_or(Types::Nil)
+    }
+
+    //the names in scope at the cursor, inner declarations hide outer ones and built ins
+    fn completions(&self, analysis: &Analysis, at: Position) -> Vec<Types> {
+        let mut visible: Vec<&Binding> = analysis
+            .bindings
+            .iter()
+            .filter(|binding| analysis.visible(binding, at))
+            .collect();
+        visible.sort_by(|a, b| {
+            b.depth
+                .cmp(&a.depth)
+                .then(position(&b.name).cmp(&position(&a.name)))
+        });
+        let mut seen = HashSet::new();
+        let mut items = Vec::new();
+        for binding in visible {
+            if seen.insert(binding.name.lexeme.clone()) {
+                items.push(object(vec![
+                    ("label", string(&binding.name.lexeme)),
+                    ("kind", Types::Int(completion_kind(binding))),
+                    ("detail", string(&signature(binding))),
+                ]));
+            }
+        }
+        for builtin in &self.builtins {
+            if seen.insert(builtin.name.clone()) {
+                items.push(object(vec![
+                    ("label", string(&builtin.name)),
+                    ("kind", Types::Int(COMPLETION_FUNCTION)),
+                    (
+                        "detail",
+                        string(&arguments(builtin.arity, builtin.optional)),
+                    ),
+                ]));
+            }
+        }
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stdlib::json;
+    use std::io::Cursor;
+
+    const URI: &str = "file:///work/main.lox";
+
+    const SOURCE: &str = "var count = 0;
+/// Adds `step` to the count.
+fun add(step) {
+    var next = count + step;
+    count = next;
+    return next;
+}
+add(2);
+print count;
+";
+
+    //frames each message the way an editor would, requests get ids and notifications do not
+    fn client(messages: &[(Option<i64>, &str, String)]) -> Cursor<Vec<u8>> {
+        let mut input = String::new();
+        for (id, method, params) in messages {
+            let id = id.map(|id| format!("\"id\": {}, ", id)).unwrap_or_default();
+            let body = format!(
+                "{{\"jsonrpc\": \"2.0\", {}\"method\": \"{}\", \"params\": {}}}",
+                id, method, params
+            );
+            input.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+        }
+        Cursor::new(input.into_bytes())
+    }
+
+    fn open(source: &str) -> (Option<i64>, &'static str, String) {
+        let text = json::stringify(&string(source), None).unwrap();
+        let params = format!(
+            "{{\"textDocument\": {{\"uri\": \"{}\", \"languageId\": \"lox\", \"version\": 1, \"text\": {}}}}}",
+            URI, text
+        );
+        (None, "textDocument/didOpen", params)
+    }
+
+    //a request about the position `line`, `character` of the document, both from 0
+    fn at(
+        id: i64,
+        method: &'static str,
+        line: i64,
+        character: i64,
+    ) -> (Option<i64>, &'static str, String) {
+        let params = format!(
+            "{{\"textDocument\": {{\"uri\": \"{}\"}}, \"position\": {{\"line\": {}, \"character\": {}}}, \
+             \"context\": {{\"includeDeclaration\": true}}}}",
+            URI, line, character
+        );
+        (Some(id), method, params)
+    }
+
+    //runs the server over the messages, returns its exit status and replies by id or method
+    fn session(messages: &[(Option<i64>, &str, String)]) -> (i32, Vec<Types>) {
+        let mut out = Vec::new();
+        let code = serve(client(messages), &mut out);
+        let mut replies = Vec::new();
+        let mut input = Cursor::new(out);
+        while let Some(reply) = protocol::read(&mut input) {
+            replies.push(reply);
+        }
+        (code, replies)
+    }
+
+    fn result(replies: &[Types], id: i64) -> Types {
+        let reply = replies
+            .iter()
+            .find(|reply| int_field(reply, "id") == Some(id))
+            .unwrap();
+        field(reply, "result").unwrap()
+    }
+
+    fn path(value: &Types, keys: &[&str]) -> Types {
+        keys.iter().fold(value.clone(), |value, key| {
+            match (&value, key.parse::<usize>()) {
+                (Types::List(items), Ok(i)) => items.borrow()[i].clone(),
+                _ => field(&value, key).unwrap(),
+            }
+        })
+    }
+
+    //(line, character) where each location of a list starts
+    fn starts(locations: &Types) -> Vec<(i64, i64)> {
+        match locations {
+            Types::List(items) => items
+                .borrow()
+                .iter()
+                .map(|location| {
+                    let start = path(location, &["range", "start"]);
+                    (
+                        int_field(&start, "line").unwrap(),
+                        int_field(&start, "character").unwrap(),
+                    )
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn labels(items: &Types) -> Vec<String> {
+        match items {
+            Types::List(items) => items
+                .borrow()
+                .iter()
+                .map(|item| text_field(item, "label").unwrap())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_navigation_uses_the_resolver() {
+        let (code, replies) = session(&[
+            (Some(1), "initialize", "{\"capabilities\": {}}".to_string()),
+            (None, "initialized", "{}".to_string()),
+            open(SOURCE),
+            at(2, "textDocument/definition", 3, 16),
+            at(3, "textDocument/references", 0, 4),
+            at(4, "textDocument/hover", 7, 1),
+            at(5, "textDocument/documentSymbol", 0, 0),
+            at(6, "textDocument/definition", 8, 2),
+            (Some(7), "shutdown", "null".to_string()),
+            (None, "exit", "null".to_string()),
+        ]);
+        assert_eq!(code, 0);
+        assert!(
+            path(&result(&replies, 1), &["capabilities", "hoverProvider"])
+                .equals(&Types::Boolean(true))
+        );
+        // opening the document publishes its (empty) diagnostics
+        assert!(path(&replies[1], &["method"]).equals(&string("textDocument/publishDiagnostics")));
+        assert_eq!(starts(&path(&replies[1], &["params", "diagnostics"])), []);
+
+        // `count` inside `add` is the global on the first line
+        let definition = result(&replies, 2);
+        assert!(path(&definition, &["uri"]).equals(&string(URI)));
+        assert_eq!(starts(&list(vec![definition])), [(0, 4)]);
+        assert_eq!(
+            starts(&result(&replies, 3)),
+            [(0, 4), (3, 15), (4, 4), (8, 6)]
+        );
+
+        let hover = text_field(&path(&result(&replies, 4), &["contents"]), "value").unwrap();
+        assert_eq!(
+            hover,
+            "```lox\nfun add(step)\n```\nTakes 1 argument.\n\nAdds `step` to the count."
+        );
+
+        let symbols = result(&replies, 5);
+        assert!(path(&symbols, &["0", "name"]).equals(&string("add")));
+        assert!(path(&symbols, &["0", "kind"]).equals(&Types::Int(SYMBOL_FUNCTION)));
+        // `print` is not a name, there is nothing to go to
+        assert!(result(&replies, 6).equals(&Types::Nil));
+    }
+
+    #[test]
+    fn test_completion_offers_names_in_scope() {
+        let (_, replies) = session(&[
+            open(SOURCE),
+            at(1, "textDocument/completion", 4, 4),
+            at(2, "textDocument/completion", 7, 0),
+            at(3, "textDocument/hover", 8, 0),
+        ]);
+        let inside = labels(&result(&replies, 1));
+        assert_eq!(inside[..4], ["next", "step", "add", "count"]);
+        assert!(inside.contains(&"clock".to_string()));
+        let outside = labels(&result(&replies, 2));
+        assert!(!outside.contains(&"step".to_string()) && !outside.contains(&"next".to_string()));
+        assert_eq!(outside[..2], ["add", "count"]);
+        assert!(result(&replies, 3).equals(&Types::Nil));
+    }
+
+    #[test]
+    fn test_changes_publish_diagnostics() {
+        let change = format!(
+            "{{\"textDocument\": {{\"uri\": \"{}\", \"version\": 2}}, \"contentChanges\": [{{\"text\": \"var a = ;\\nvar b = 1;\\nprint b;\\nprint ;\"}}]}}",
+            URI
+        );
+        let (code, replies) = session(&[
+            open(SOURCE),
+            (None, "textDocument/didChange", change),
+            at(1, "textDocument/definition", 2, 6),
+            (Some(2), "workspace/symbol", "{}".to_string()),
+        ]);
+        // the editor went away without a shutdown
+        assert_eq!(code, 1);
+        let diagnostics = path(&replies[1], &["params", "diagnostics"]);
+        assert_eq!(starts(&diagnostics), [(0, 8), (3, 6)]);
+        assert!(path(&diagnostics, &["0", "message"]).equals(&string("Expect expression.")));
+        assert!(path(&diagnostics, &["1", "severity"]).equals(&Types::Int(SEVERITY_ERROR)));
+        // the parser recovered, the rest of the document can still be asked about
+        assert_eq!(starts(&list(vec![result(&replies, 1)])), [(1, 4)]);
+        let error = path(&replies[3], &["error", "code"]);
+        assert!(error.equals(&Types::Int(METHOD_NOT_FOUND)));
+    }
+
+    #[test]
+    fn test_invalid_assignment_is_a_diagnostic() {
+        let (code, replies) = session(&[
+            open("var a = 1;\n1 = 2;\nprint a;\n"),
+            at(1, "textDocument/definition", 2, 6),
+            (Some(2), "shutdown", "null".to_string()),
+            (None, "exit", "null".to_string()),
+        ]);
+        assert_eq!(code, 0);
+        let diagnostics = path(&replies[0], &["params", "diagnostics"]);
+        assert_eq!(starts(&diagnostics), [(1, 2)]);
+        assert!(path(&diagnostics, &["0", "message"]).equals(&string("Invalid assignment target.")));
+        assert_eq!(starts(&list(vec![result(&replies, 1)])), [(0, 4)]);
+    }
+
+    #[test]
+    fn test_positions_count_utf16_units() {
+        let (code, replies) = session(&[
+            open("var s = \"😀é\"; var x = 1;\nprint x;\n\"😀\" + ;\n"),
+            at(1, "textDocument/definition", 1, 6),
+            at(2, "textDocument/references", 0, 19),
+            at(3, "textDocument/definition", -1, 6),
+            at(4, "textDocument/references", 0, -19),
+            (Some(5), "shutdown", "null".to_string()),
+            (None, "exit", "null".to_string()),
+        ]);
+        assert_eq!(code, 0);
+        // the emoji is two units and `é` one, though they take six bytes
+        let definition = result(&replies, 1);
+        assert_eq!(starts(&list(vec![definition.clone()])), [(0, 19)]);
+        let end = path(&definition, &["range", "end"]);
+        assert!(path(&end, &["character"]).equals(&Types::Int(20)));
+        assert_eq!(starts(&result(&replies, 2)), [(0, 19), (1, 6)]);
+        let diagnostics = path(&replies[0], &["params", "diagnostics"]);
+        assert_eq!(starts(&diagnostics), [(2, 7)]);
+        // a negative position is answered with null rather than a crash
+        assert!(result(&replies, 3).equals(&Types::Nil));
+        assert!(result(&replies, 4).equals(&Types::Nil));
+    }
+}
@@ -1,50 +1,99 @@
 mod ast;
 mod astprinter;
 mod class;
+mod dap;
+mod debugger;
 mod environment;
 mod error;
+mod formatter;
 mod function;
+mod heap;
 mod interpreter;
+mod lsp;
+mod map;
+mod module;
 mod parser;
+mod protocol;
 mod resolver;
 mod scanner;
+mod stdlib;
+mod test_runner;
 pub mod token;
 
 use crate::ast::{Expr, Stmt};
+use crate::error::Error;
+use crate::interpreter::Capabilities;
 use crate::parser::Parser;
 use crate::token::{Token, TokenType};
 use log::debug;
 use resolver::Resolver;
-use std::io::stdin;
+use std::io::{self, stdin};
 use std::process::exit;
+use std::thread;
 use std::{env, fs, process};
 
 static mut HAD_ERROR: bool = false;
+
+//every Lox call recurses through the tree walker, a large stack lets scripts reach the call
+//depth limit and fail with "Stack overflow." before the native stack runs out
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
 fn main() {
+    let cli = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_cli)
+        .expect("could not start the interpreter thread");
+    if cli.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn run_cli() {
     let args: Vec<String> = env::args().collect();
+    // capability flags may appear anywhere, what is left is the command and its files
+    let capabilities = Capabilities {
+        fs: args.iter().any(|arg| arg == "--allow-fs"),
+        env: true,
+        imports: true,
+    };
+    let args: Vec<String> = args.into_iter().filter(|arg| arg != "--allow-fs").collect();
 
-    if args.len() > 4 {
-        println!("Usage: rlox [script]");
+    if args.get(1).map(String::as_str) == Some("fmt") {
+        process::exit(run_fmt(&args[2..]));
+    }
+    if args.get(1).map(String::as_str) == Some("test") {
+        process::exit(run_tests(&args[2..], capabilities));
+    }
+    if args.get(1).map(String::as_str) == Some("debug") {
+        process::exit(run_debug(&args[2..], capabilities));
+    }
+    if args.get(1).map(String::as_str) == Some("dap") {
+        process::exit(dap::serve(io::stdin().lock(), io::stdout(), capabilities));
+    }
+    if args.get(1).map(String::as_str) == Some("lsp") {
+        process::exit(lsp::serve(io::stdin().lock(), io::stdout()));
+    }
 
-        process::exit(1)
-    } else if args.len() == 3 {
+    if args.len() >= 3 {
+        // everything after the script path is handed to the script as `args()`
         let path = args.get(2);
-        println!("{:?}", path);
         match path {
-            Some(x) => {
-                run_file(x).unwrap_or_else(|_| exit(1));
-            }
+            Some(x) => match run_file(x, &args[3..], capabilities) {
+                Ok(0) => {}
+                Ok(status) => exit(status),
+                Err(_) => exit(1),
+            },
             None => {
                 println!("Usage: rlox [script]");
                 process::exit(1)
             }
         }
     } else if args.len() == 1 {
-        run_prompt().expect("UNABLE TO READ LINE")
+        run_prompt(capabilities).expect("UNABLE TO READ LINE")
     }
 }
 
-pub fn run_prompt() -> Result<(), Box<dyn std::error::Error + 'static>> {
+pub fn run_prompt(capabilities: Capabilities) -> Result<(), Box<dyn std::error::Error + 'static>> {
     let mut buffer = String::new();
 
     loop {
@@ -56,12 +105,19 @@ pub fn run_prompt() -> Result<(), Box<dyn std::error::Error + 'static>> {
             }
             line => line,
         };
-        run(res);
+        if let Status::Exit(code) = run(res, None, &[], capabilities) {
+            exit(code);
+        }
         buffer.clear();
     }
 }
 
-pub fn run_file(my_str: &str) -> Result<(), Box<dyn std::error::Error + 'static>> {
+//returns the exit status of the script, 65 for compile errors and 70 for runtime errors
+pub fn run_file(
+    my_str: &str,
+    script_args: &[String],
+    capabilities: Capabilities,
+) -> Result<i32, Box<dyn std::error::Error + 'static>> {
     let bytes = fs::read_to_string(my_str);
     match bytes {
         Ok(token_string) => {
@@ -73,8 +129,13 @@ pub fn run_file(my_str: &str) -> Result<(), Box<dyn std::error::Error + 'static>
             //     unsafe {HAD_ERROR= false}
             // }
             // println!("{:?}", current_lines);
-            run(&current_lines);
-            Ok(())
+            Ok(run(
+                &current_lines,
+                Some(std::path::Path::new(my_str)),
+                script_args,
+                capabilities,
+            )
+            .code())
         }
         Err(e) => {
             if e.kind() == std::io::ErrorKind::Interrupted {
@@ -86,28 +147,166 @@ pub fn run_file(my_str: &str) -> Result<(), Box<dyn std::error::Error + 'static>
     }
 }
 
-pub fn run(token_stream: &[u8]) {
-    let mut scanner = scanner::Scanner::new(&*token_stream);
+//rewrites each file in canonical style, with `--check` only lists the files that would change
+pub fn run_fmt(args: &[String]) -> i32 {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+    if paths.is_empty() {
+        println!("Usage: rlox fmt [--check] [files]");
+        return 64;
+    }
+
+    let mut status = 0;
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                status = 1;
+                continue;
+            }
+        };
+        match formatter::format_source(&source) {
+            Ok(formatted) if formatted == source => {}
+            Ok(formatted) => {
+                if check {
+                    println!("{}", path);
+                    status = 1;
+                } else if let Err(e) = fs::write(path, formatted) {
+                    eprintln!("{}: {}", path, e);
+                    status = 1;
+                }
+            }
+            Err(_) => {
+                eprintln!("{}: could not be parsed, left unformatted", path);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+//runs the test blocks of every .lox file in the given directories
+pub fn run_tests(args: &[String], capabilities: Capabilities) -> i32 {
+    if args.is_empty() {
+        println!("Usage: rlox test [dir]");
+        return 64;
+    }
+    let mut failed = 0;
+    for dir in args {
+        failed += test_runner::run_tests(std::path::Path::new(dir), capabilities).failed;
+    }
+    if failed > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+//runs a script under the command line debugger, the session talks over stdin and stdout
+pub fn run_debug(args: &[String], capabilities: Capabilities) -> i32 {
+    let path = match args.first() {
+        Some(path) => path,
+        None => {
+            println!("Usage: rlox debug [script] [args]");
+            return 64;
+        }
+    };
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            return 66;
+        }
+    };
+    let mut interpreter = interpreter::Interpreter::new();
+    interpreter.capabilities = capabilities;
+    interpreter.set_args(args[1..].to_vec());
+    interpreter.set_script_path(std::path::Path::new(path));
+    let session = debugger::Session::new(io::stdin().lock(), io::stdout());
+    interpreter.set_debugger(Box::new(session));
+    run_with(&mut interpreter, source.as_bytes()).code()
+}
+
+//how a run ended, `code` is what the process exits with
+pub enum Status {
+    Done,
+    CompileError,
+    RuntimeError,
+    Exit(i32),
+}
+
+impl Status {
+    pub fn code(&self) -> i32 {
+        match self {
+            Status::Done => 0,
+            Status::CompileError => 65,
+            Status::RuntimeError => 70,
+            Status::Exit(code) => *code,
+        }
+    }
+}
+
+//`script` is the file the source came from, imports are resolved relative to it
+pub fn run(
+    token_stream: &[u8],
+    script: Option<&std::path::Path>,
+    script_args: &[String],
+    capabilities: Capabilities,
+) -> Status {
+    let mut interpreter = interpreter::Interpreter::new();
+    interpreter.capabilities = capabilities;
+    interpreter.set_args(script_args.to_vec());
+    if let Some(path) = script {
+        interpreter.set_script_path(path);
+    }
+    run_with(&mut interpreter, token_stream)
+}
+
+//compiles and runs source on an interpreter the caller has set up
+pub fn run_with(interpreter: &mut interpreter::Interpreter, token_stream: &[u8]) -> Status {
+    let mut scanner = scanner::Scanner::new(token_stream);
     let tokens = scanner.scan_tokens();
 
     let mut parser: Parser = Parser::new(tokens);
     let statements: Vec<Stmt> = parser.parse().unwrap();
-    let mut interpreter = interpreter::Interpreter::new();
-    let mut resolver = Resolver::new(&mut interpreter);
+    if scanner.had_error() || parser.had_error() {
+        return Status::CompileError;
+    }
+    let mut resolver = Resolver::new(interpreter);
     resolver.resolve_stmts(&statements);
-    interpreter.interpret(statements);
+    if resolver.had_error() {
+        return Status::CompileError;
+    }
+    match interpreter.interpret(statements) {
+        Err(Error::RunTime { token, message }) => {
+            error::runtime_error(&token, &message);
+            Status::RuntimeError
+        }
+        Err(Error::Throw { token, value }) => {
+            let value = interpreter.stringify(value);
+            error::runtime_error(&token, &format!("Uncaught exception: {}", value));
+            Status::RuntimeError
+        }
+        Err(Error::Exit { code }) => Status::Exit(code),
+        Err(Error::Limit { message }) => {
+            eprintln!("{}", message);
+            Status::RuntimeError
+        }
+        _ => Status::Done,
+    }
 }
 
 pub fn error(token: Token, message: &str) {
     if token.t_type == TokenType::EOF {
-        report(token.line, "at end", message)
+        report(token.line, " at end", message)
     } else {
-        report(token.line, &*token.lexeme, message);
+        report(token.line, &format!(" at '{}'", token.lexeme), message);
     }
 }
 
 pub fn report(line: u32, where_line: &str, message: &str) {
-    eprintln!("[line {:?} ] Error {:?} :  {:?}", line, where_line, message);
+    eprintln!("[line {}] Error{}: {}", line, where_line, message);
     unsafe {
         HAD_ERROR = true;
     }
@@ -0,0 +1,156 @@
+use crate::interpreter::Types;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+//A map that remembers insertion order, what `json_parse` turns objects into
+//keys are any values compared with `Types::equals`, string keys are also indexed by their text
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    index: HashMap<String, usize>,
+    entries: Vec<(Types, Types)>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //where `key` sits in the entries, other keys are searched one by one
+    fn position(&self, key: &Types) -> Option<usize> {
+        match key {
+            Types::ReturnString(text) => self.index.get(text).copied(),
+            _ => self.entries.iter().position(|(k, _)| k.equals(key)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Types> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn get_key(&self, key: &Types) -> Option<&Types> {
+        self.position(key).map(|i| &self.entries[i].1)
+    }
+
+    pub fn insert(&mut self, key: String, value: Types) {
+        self.insert_key(Types::ReturnString(key), value)
+    }
+
+    //replaces the value of an existing key in place, keeping its position
+    pub fn insert_key(&mut self, key: Types, value: Types) {
+        match self.position(&key) {
+            Some(i) => self.entries[i].1 = value,
+            None => {
+                if let Types::ReturnString(text) = &key {
+                    self.index.insert(text.clone(), self.entries.len());
+                }
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Types> {
+        self.remove_key(&Types::ReturnString(key.to_string()))
+    }
+
+    pub fn remove_key(&mut self, key: &Types) -> Option<Types> {
+        let i = self.position(key)?;
+        if let Types::ReturnString(text) = key {
+            self.index.remove(text);
+        }
+        let (_, value) = self.entries.remove(i);
+        for position in self.index.values_mut() {
+            if *position > i {
+                *position -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.index.contains_key(key)
+    }
+
+    pub fn contains_key(&self, key: &Types) -> bool {
+        self.position(key).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Types, &Types)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    //same keys with equal values, the order they were inserted in does not matter
+    pub fn equals(&self, other: &Map) -> bool {
+        self.equals_within(other, &mut Vec::new())
+    }
+
+    //see `Types::equals_within`
+    pub(crate) fn equals_within(&self, other: &Map, compared: &mut Vec<(*const (), *const ())>) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(key, value)| {
+                other
+                    .get_key(key)
+                    .is_some_and(|v| value.equals_within(v, compared))
+            })
+    }
+
+    //see `Types::fmt_within`
+    pub(crate) fn fmt_within(&self, f: &mut fmt::Formatter<'_>, open: &mut Vec<*const ()>) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, (key, value)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            key.fmt_within(f, open)?;
+            write!(f, ": ")?;
+            value.fmt_within(f, open)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_within(f, &mut Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_insertion_order_across_removal() {
+        let mut map = Map::new();
+        map.insert("b".to_string(), Types::Int(1));
+        map.insert("a".to_string(), Types::Int(2));
+        map.insert("c".to_string(), Types::Int(3));
+        map.insert("b".to_string(), Types::Int(4));
+        assert!(map.remove("a").is_some());
+        let keys: Vec<String> = map.iter().map(|(key, _)| key.to_string()).collect();
+        assert_eq!(keys, ["\"b\"", "\"c\""]);
+        assert!(map.get("c").unwrap().equals(&Types::Int(3)));
+        assert!(map.get("b").unwrap().equals(&Types::Int(4)));
+    }
+
+    #[test]
+    fn test_keys_compare_with_equals() {
+        let mut map = Map::new();
+        map.insert_key(Types::Int(1), Types::ReturnString("one".to_string()));
+        map.insert_key(Types::Number(1.0), Types::ReturnString("uno".to_string()));
+        map.insert_key(Types::Boolean(true), Types::Nil);
+        map.insert("1".to_string(), Types::Int(1));
+        assert_eq!(map.len(), 3);
+        assert!(map.get_key(&Types::Int(1)).unwrap().equals(&Types::ReturnString("uno".to_string())));
+        assert!(map.remove_key(&Types::Boolean(true)).is_some());
+        assert!(!map.contains_key(&Types::Boolean(true)));
+        assert!(map.get("1").unwrap().equals(&Types::Int(1)));
+    }
+}
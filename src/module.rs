@@ -0,0 +1,72 @@
+use crate::environment::Environment;
+use crate::error::Error;
+use crate::interpreter::Types;
+use crate::token::{Token, TokenType};
+use std::fmt::{self, Debug, Display};
+use std::path::{Path, PathBuf};
+
+//A file loaded by `import`, its top-level definitions live in `env`
+pub struct Module {
+    pub name: String,
+    pub path: PathBuf,
+    env: Environment,
+}
+
+impl Module {
+    pub fn new(name: String, path: PathBuf, env: Environment) -> Self {
+        Self { name, path, env }
+    }
+
+    pub fn env(&self) -> &Environment {
+        &self.env
+    }
+
+    //a top-level definition of the module, `math.sqrt` or `import { sqrt } from ...`
+    pub fn get(&self, name: &Token) -> Result<Types, Error> {
+        self.env.get_own(&name.lexeme).ok_or_else(|| Error::RunTime {
+            token: name.clone(),
+            message: format!("Module '{}' has no member '{}'.", self.name, name.lexeme),
+        })
+    }
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+impl Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+//the path written in the import statement
+pub fn import_path(path: &Token) -> String {
+    match &path.t_type {
+        TokenType::String(s) => s.clone(),
+        _ => path.lexeme.clone(),
+    }
+}
+
+//the name `import "lib/math.lox";` binds the module to, here `math`
+pub fn binding_name(path: &Token) -> Token {
+    let stem = Path::new(&import_path(path))
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Token {
+        t_type: TokenType::Identifier(stem.clone()),
+        lexeme: stem,
+        line: path.line,
+        column: path.column,
+    }
+}
+
+//how a module file is named in error messages
+pub fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
@@ -1,11 +1,11 @@
-use crate::ast::Expr::Literal;
 use crate::ast::Stmt::IfStmt;
 use crate::ast::{Expr, Stmt};
 use crate::error::{parse_error, Error};
 use crate::interpreter::Types;
-use crate::token::TokenType::{False, LeftParen, Return, RightParen, SemiColon, True};
+use crate::token::TokenType::{False, LeftParen, Return, RightParen, SemiColon};
 use crate::token::{Token, TokenType};
 use log::debug;
+use std::collections::HashMap;
 use std::io::ErrorKind::Other;
 
 //TODO match macro
@@ -27,11 +27,34 @@ macro_rules! matches {
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    had_error: bool,
+    // doc comments keyed by the index of the token that follows them
+    docs: HashMap<usize, String>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        let mut docs = HashMap::new();
+        let mut pending: Vec<String> = Vec::new();
+        let mut stripped = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            match token.t_type {
+                TokenType::DocComment(text) => pending.push(text),
+                _ => {
+                    if !pending.is_empty() {
+                        docs.insert(stripped.len(), pending.join("\n"));
+                        pending.clear();
+                    }
+                    stripped.push(token);
+                }
+            }
+        }
+        Self {
+            tokens: stripped,
+            current: 0,
+            had_error: false,
+            docs,
+        }
     }
 
     //returns an expression tree from the Vector
@@ -43,15 +66,26 @@ impl Parser {
         return Ok(statements);
     }
 
+    //whether any statement failed to parse and was replaced by Stmt::Null
+    pub fn had_error(&self) -> bool {
+        self.had_error
+    }
+
     fn declaration(&mut self) -> Result<Stmt, Error> {
         let statement = match self.peek().unwrap().t_type {
             TokenType::Var => self.var_declaration(),
             TokenType::Class => self.class_declaration(),
             TokenType::Fun => self.function_declaration("function"),
+            TokenType::Import => self.import_declaration(),
+            // `test` is only a keyword when a name string follows it
+            TokenType::Identifier(ref name) if name == "test" && self.next_is_string() => {
+                self.test_declaration()
+            }
             _ => self.statement(),
         };
         match statement {
             Err(Error::Parse) => {
+                self.had_error = true;
                 self.synchronize();
                 Ok(Stmt::Null)
             }
@@ -60,6 +94,7 @@ impl Parser {
     }
 
     fn function_declaration(&mut self, kind: &str) -> Result<Stmt, Error> {
+        let doc = self.docs.get(&self.current).cloned();
         let name = self.consume_identifier(format!("Expect {} name.", kind).as_str())?;
         self.consume(
             TokenType::LeftParen,
@@ -87,10 +122,68 @@ impl Parser {
             format!("Expect '{{' before {} body.", kind).as_str(),
         )?;
         let body = self.block_statement()?;
-        Ok(Stmt::Function(name, params, body))
+        Ok(Stmt::Function(name, params, body, doc))
+    }
+
+    fn test_declaration(&mut self) -> Result<Stmt, Error> {
+        self.advance();
+        let name = self.advance().unwrap().clone();
+        self.consume(TokenType::LeftBrace, "Expect '{' before test body.")?;
+        let body = self.block_statement()?;
+        Ok(Stmt::Test(name, body))
+    }
+
+    fn import_declaration(&mut self) -> Result<Stmt, Error> {
+        self.advance();
+        let mut names = Vec::new();
+        if matches!(self, TokenType::LeftBrace) {
+            loop {
+                names.push(self.identifier("Expect name to import.")?);
+                if !matches!(self, TokenType::Comma) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightBrace, "Expect '}' after imported names.")?;
+            if !self.check_contextual("from") {
+                return Err(self.error(self.peek().unwrap(), "Expect 'from' after imported names."));
+            }
+            self.advance();
+        }
+        let path = match self.peek().unwrap().t_type {
+            TokenType::String(_) => self.advance().unwrap().clone(),
+            _ => return Err(self.error(self.peek().unwrap(), "Expect module path string.")),
+        };
+        let mut alias = None;
+        if names.is_empty() && self.check_contextual("as") {
+            self.advance();
+            alias = Some(self.identifier("Expect module name after 'as'.")?);
+        }
+        self.consume(TokenType::SemiColon, "Expect ';' after import.")?;
+        Ok(Stmt::Import(path, alias, names))
+    }
+
+    //consumes the current token if it is an identifier
+    fn identifier(&mut self, error_msg: &str) -> Result<Token, Error> {
+        match self.peek().unwrap().t_type {
+            TokenType::Identifier(_) => Ok(self.advance().unwrap().clone()),
+            _ => Err(self.error(self.peek().unwrap(), error_msg)),
+        }
+    }
+
+    //`from` and `as` are only keywords inside an import
+    fn check_contextual(&self, word: &str) -> bool {
+        std::matches!(&self.peek().unwrap().t_type, TokenType::Identifier(name) if name == word)
+    }
+
+    fn next_is_string(&self) -> bool {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => std::matches!(token.t_type, TokenType::String(_)),
+            None => false,
+        }
     }
 
     fn class_declaration(&mut self) -> Result<Stmt, Error> {
+        let doc = self.docs.get(&self.current).cloned();
         let name = self.consume_identifier("Expect class name.")?;
 
         self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
@@ -100,7 +193,7 @@ impl Parser {
         }
         self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
 
-        Ok(Stmt::Class(name, methods))
+        Ok(Stmt::Class(name, methods, doc))
     }
 
     fn var_declaration(&mut self) -> Result<Stmt, Error> {
@@ -112,12 +205,10 @@ impl Parser {
             None
         };
 
-        let _ = self
-            .consume(
-                self.peek().unwrap().t_type.clone(),
-                "Expected `;` after variable declaration",
-            )
-            .unwrap();
+        self.consume(
+            TokenType::SemiColon,
+            "Expected `;` after variable declaration",
+        )?;
         Ok(Stmt::VarDeclaration(token, initializer))
     }
 
@@ -140,6 +231,14 @@ impl Parser {
                 self.advance();
                 self.return_statement()
             }
+            TokenType::Throw => {
+                self.advance();
+                self.throw_statement()
+            }
+            TokenType::Try => {
+                self.advance();
+                self.try_statement()
+            }
             TokenType::While => {
                 self.advance();
                 self.while_statement()
@@ -158,7 +257,10 @@ impl Parser {
 
         let initializer = match self.peek().unwrap().t_type {
             TokenType::Var => Some(self.var_declaration()?),
-            TokenType::SemiColon => None,
+            TokenType::SemiColon => {
+                self.advance();
+                None
+            }
             _ => Some(self.expression_statement()?),
         };
         let condition = if !self.check(TokenType::SemiColon) {
@@ -175,45 +277,29 @@ impl Parser {
         };
         self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
 
-        let mut body = self.statement()?;
-
-        if let Some(inc) = increment {
-            let inc_stmt = Stmt::Expr(inc);
-            body = Stmt::Block(vec![body, inc_stmt])
-        };
+        let body = self.statement()?;
 
-        body = Stmt::While(
-            condition.unwrap_or(Literal {
-                token: Token {
-                    t_type: True,
-                    lexeme: "".to_string(),
-                    line: self.peek().unwrap().line.clone(),
-                },
-            }),
+        Ok(Stmt::For(
+            initializer.map(Box::new),
+            condition,
+            increment,
             Box::new(body),
-        );
-
-        if let Some(init_stmt) = initializer {
-            body = Stmt::Block(vec![init_stmt, body])
-        }
-        Ok(body)
+        ))
     }
     fn if_statement(&mut self) -> Result<Stmt, Error> {
         self.consume(LeftParen, "Expect '(' after 'if'.")?;
         let expr = self.expression()?;
         self.consume(RightParen, "Expect ')' after if condition.")?;
         let then_stmt = self.statement()?;
-        match self.advance().unwrap().t_type.clone() {
-            TokenType::Else => {
-                let else_branch = self.statement()?;
-                return Ok(IfStmt(
-                    expr,
-                    Box::new(then_stmt),
-                    Some(Box::new(else_branch)),
-                ));
-            }
-            _ => return Ok(IfStmt(expr, Box::new(then_stmt), None)),
-        };
+        if matches!(self, TokenType::Else) {
+            let else_branch = self.statement()?;
+            return Ok(IfStmt(
+                expr,
+                Box::new(then_stmt),
+                Some(Box::new(else_branch)),
+            ));
+        }
+        Ok(IfStmt(expr, Box::new(then_stmt), None))
     }
 
     fn while_statement(&mut self) -> Result<Stmt, Error> {
@@ -236,6 +322,37 @@ impl Parser {
         Ok(Stmt::Return(keyword.clone(), value))
     }
 
+    fn throw_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous().unwrap().clone();
+        let value = self.expression()?;
+        self.consume(SemiColon, "Expect ';' after thrown value.")?;
+        Ok(Stmt::Throw(keyword, value))
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous().unwrap().clone();
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.")?;
+        let body = self.block_statement()?;
+
+        let mut catch = None;
+        if matches!(self, TokenType::Catch) {
+            self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.")?;
+            let name = self.identifier("Expect error variable name.")?;
+            self.consume(TokenType::RightParen, "Expect ')' after error variable.")?;
+            self.consume(TokenType::LeftBrace, "Expect '{' before catch body.")?;
+            catch = Some((name, self.block_statement()?));
+        }
+        let mut finally = None;
+        if matches!(self, TokenType::Finally) {
+            self.consume(TokenType::LeftBrace, "Expect '{' after 'finally'.")?;
+            finally = Some(self.block_statement()?);
+        }
+        if catch.is_none() && finally.is_none() {
+            return Err(self.error(self.peek().unwrap(), "Expect 'catch' or 'finally' after try block."));
+        }
+        Ok(Stmt::Try(keyword, body, catch, finally))
+    }
+
     fn print_statement(&mut self) -> Result<Stmt, Error> {
         let expr = self.expression()?;
         self.consume(TokenType::SemiColon, "Expect ';' after value.")?;
@@ -256,8 +373,7 @@ impl Parser {
         } {
             statements.push(self.declaration()?)
         }
-        self.consume(TokenType::RightBrace, "Expect '}' after block.")
-            .unwrap();
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
         return Ok(statements);
     }
     //using recursive decent parsing method
@@ -266,12 +382,13 @@ impl Parser {
     }
 
     fn assignment(&mut self) -> Result<Expr, Error> {
-        let expr = self.or()?;
+        let expr = self.ternary()?;
 
-        if let Some(_) = match self.peek().unwrap().t_type {
+        if let Some(t) = match self.peek().unwrap().t_type {
             TokenType::Equal => self.advance(),
             _ => None,
         } {
+            let equals = t.clone();
             let value = self.assignment()?;
             match expr {
                 Expr::Variable { name, .. } => {
@@ -280,11 +397,65 @@ impl Parser {
                         value: Box::new(value),
                     })
                 }
-                _ => panic!("unable to assign "),
+                _ => return Err(self.error(&equals, "Invalid assignment target.")),
             }
         }
+
+        if let Some(t) = match self.peek().unwrap().t_type {
+            TokenType::PlusEqual
+            | TokenType::MinusEqual
+            | TokenType::StarEqual
+            | TokenType::SlashEqual
+            | TokenType::PercentEqual => self.advance(),
+            _ => None,
+        } {
+            let op = t.clone();
+            let value = self.assignment()?;
+            return match expr {
+                Expr::Variable { name } => Ok(Expr::CompoundAssign {
+                    name,
+                    op,
+                    value: Box::new(value),
+                }),
+                _ => Err(self.error(&op, "Invalid assignment target.")),
+            };
+        }
         return Ok(expr);
     }
+    // `cond ? a : b`, right associative so `a ? b : c ? d : e` nests in the else branch
+    fn ternary(&mut self) -> Result<Expr, Error> {
+        let condition = self.coalesce()?;
+        if !matches!(self, TokenType::Question) {
+            return Ok(condition);
+        }
+        let then_branch = self.expression()?;
+        self.consume(TokenType::Colon, "Expect ':' after then branch of conditional expression.")?;
+        let else_branch = self.ternary()?;
+        Ok(Expr::Ternary {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        })
+    }
+
+    // `a ?? b` short circuits like `or` but only skips `b` when `a` isn't nil
+    fn coalesce(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.or()?;
+        while let Some(t) = match self.peek().unwrap().t_type {
+            TokenType::QuestionQuestion => self.advance(),
+            _ => None,
+        } {
+            let operator = t.clone();
+            let right = self.or()?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                op: operator,
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
     fn or(&mut self) -> Result<Expr, Error> {
         let mut expr = self.and()?;
 
@@ -343,7 +514,7 @@ impl Parser {
 
     //checks whether the given expression is comparison operation returns an expression
     fn comparison(&mut self) -> Result<Expr, Error> {
-        let mut expr = self.term()?;
+        let mut expr = self.bit_or()?;
         //dbg!(&self.peek().unwrap());
         while let Some(t) = match self.peek().unwrap().t_type {
             TokenType::Greater
@@ -353,7 +524,7 @@ impl Parser {
             _ => None,
         } {
             let operator: Token = t.clone();
-            let right = self.term()?;
+            let right = self.bit_or()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op: operator.clone(),
@@ -363,6 +534,78 @@ impl Parser {
         return Ok(expr);
     }
 
+    //bitwise or binds looser than xor and and, all of them looser than shifts
+    fn bit_or(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.bit_xor()?;
+        while let Some(t) = match self.peek().unwrap().t_type {
+            TokenType::Pipe => self.advance(),
+            _ => None,
+        } {
+            let operator = t.clone();
+            let right = self.bit_xor()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op: operator,
+                right: Box::new(right),
+            }
+        }
+        Ok(expr)
+    }
+
+    //bitwise xor
+    fn bit_xor(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.bit_and()?;
+        while let Some(t) = match self.peek().unwrap().t_type {
+            TokenType::Caret => self.advance(),
+            _ => None,
+        } {
+            let operator = t.clone();
+            let right = self.bit_and()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op: operator,
+                right: Box::new(right),
+            }
+        }
+        Ok(expr)
+    }
+
+    //bitwise and
+    fn bit_and(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.shift()?;
+        while let Some(t) = match self.peek().unwrap().t_type {
+            TokenType::Ampersand => self.advance(),
+            _ => None,
+        } {
+            let operator = t.clone();
+            let right = self.shift()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op: operator,
+                right: Box::new(right),
+            }
+        }
+        Ok(expr)
+    }
+
+    //left and right shifts
+    fn shift(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.term()?;
+        while let Some(t) = match self.peek().unwrap().t_type {
+            TokenType::LessLess | TokenType::GreaterGreater => self.advance(),
+            _ => None,
+        } {
+            let operator = t.clone();
+            let right = self.term()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op: operator,
+                right: Box::new(right),
+            }
+        }
+        Ok(expr)
+    }
+
     //checks whether the expression is add or sub and returns an expression
     fn term(&mut self) -> Result<Expr, Error> {
         let mut expr = self.factor()?;
@@ -382,12 +625,12 @@ impl Parser {
         return Ok(expr);
     }
 
-    //checks whether the expression is mul or div and returns an expression
+    //checks whether the expression is mul, div or mod and returns an expression
     fn factor(&mut self) -> Result<Expr, Error> {
         let mut expr = self.unary()?;
         //dbg!(&self.peek().unwrap());
         while let Some(t) = match self.peek().unwrap().t_type {
-            TokenType::Star | TokenType::Slash => self.advance(),
+            TokenType::Star | TokenType::Slash | TokenType::Percent => self.advance(),
             _ => None,
         } {
             let operator = t.clone();
@@ -404,7 +647,7 @@ impl Parser {
     fn unary(&mut self) -> Result<Expr, Error> {
         //dbg!(&self.peek().unwrap());
         if let Some(t) = match self.peek().unwrap().t_type {
-            TokenType::Bang | TokenType::Minus => self.advance(),
+            TokenType::Bang | TokenType::Minus | TokenType::Tilde => self.advance(),
             _ => None,
         } {
             let operator = t.clone();
@@ -414,20 +657,59 @@ impl Parser {
                 expr: Box::new(right),
             });
         }
-        return Ok(self.callee()?);
+        self.power()
+    }
+
+    // `**` binds tighter than unary minus on its left and is right associative
+    fn power(&mut self) -> Result<Expr, Error> {
+        let expr = self.callee()?;
+        if let Some(t) = match self.peek().unwrap().t_type {
+            TokenType::StarStar => self.advance(),
+            _ => None,
+        } {
+            let operator = t.clone();
+            let right = self.unary()?;
+            return Ok(Expr::Binary {
+                left: Box::new(expr),
+                op: operator,
+                right: Box::new(right),
+            });
+        }
+        Ok(expr)
     }
     // returns a literal such as String, bool, Number and also grouping expression
 
     fn callee(&mut self) -> Result<Expr, Error> {
         let mut expr = self.primary()?;
+        let mut chained = false;
         loop {
-            if self.peek().unwrap().t_type.clone() == TokenType::LeftParen {
-                self.advance();
-                expr = self.finish_call(expr)?
-            } else {
-                break;
+            match self.peek().unwrap().t_type.clone() {
+                TokenType::LeftParen => {
+                    self.advance();
+                    expr = self.finish_call(expr)?
+                }
+                TokenType::Dot | TokenType::QuestionDot => {
+                    let optional = self.advance().unwrap().t_type == TokenType::QuestionDot;
+                    chained |= optional;
+                    let name = match self.peek().unwrap().t_type {
+                        TokenType::Identifier(_) => self.advance().unwrap().clone(),
+                        _ => return Err(self.error(self.peek().unwrap(), "Expect property name after '.'.")),
+                    };
+                    expr = Expr::Get {
+                        object: Box::new(expr),
+                        name,
+                        optional,
+                    }
+                }
+                _ => break,
             }
         }
+        // everything after the first `?.` is skipped together, so the whole chain is marked
+        if chained {
+            expr = Expr::OptionalChain {
+                expr: Box::new(expr),
+            };
+        }
         return Ok(expr);
     }
 
@@ -470,16 +752,16 @@ impl Parser {
             TokenType::String(_) => Expr::Literal {
                 token: previous_token.clone(),
             },
-            TokenType::Number(_) => Expr::Literal {
+            TokenType::Number(_) | TokenType::Int(_) => Expr::Literal {
                 token: previous_token.clone(),
             },
             TokenType::LeftParen => {
+                self.advance();
                 let expr = self.expression()?;
-                self.consume(TokenType::RightParen, "Expect ')' after expression.")
-                    .unwrap();
-                Expr::Grouping {
+                self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+                return Ok(Expr::Grouping {
                     expr: Box::new(expr),
-                }
+                });
             }
             TokenType::Identifier(_) => Expr::Variable {
                 name: previous_token.clone(),
@@ -512,7 +794,9 @@ impl Parser {
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => return,
+                | TokenType::Return
+                | TokenType::Throw
+                | TokenType::Try => return,
                 _ => self.advance(),
             };
         }
@@ -582,31 +866,37 @@ mod tests {
                 t_type: TokenType::Var,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Identifier(String::from("x")),
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Equal,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Number(5.0),
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::SemiColon,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::EOF,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
         ];
 
@@ -622,12 +912,14 @@ mod tests {
                     t_type: TokenType::Identifier(String::from("x")),
                     lexeme: String::new(),
                     line: 0,
+                    column: 0,
                 },
                 Some(Expr::Literal {
                     token: Token {
                         t_type: TokenType::Number(5.0),
                         lexeme: String::new(),
                         line: 0,
+                        column: 0,
                     },
                 })
             )]
@@ -642,61 +934,73 @@ mod tests {
                 t_type: TokenType::If,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::LeftParen,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Identifier(String::from("x")),
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::EqualEqual,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Number(5.0),
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::RightParen,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::LeftBrace,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Print,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::String(String::from("true")),
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::SemiColon,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::RightBrace,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
             Token {
                 t_type: TokenType::EOF,
                 lexeme: String::new(),
                 line: 0,
+                column: 0,
             },
         ];
 
@@ -715,18 +1019,21 @@ mod tests {
                             t_type: TokenType::Identifier(String::from("x")),
                             lexeme: String::new(),
                             line: 0,
+                            column: 0,
                         }
                     }),
                     op: Token {
                         t_type: TokenType::EqualEqual,
                         lexeme: String::new(),
                         line: 0,
+                        column: 0,
                     },
                     right: Box::new(Expr::Literal {
                         token: Token {
                             t_type: TokenType::Number(5.0),
                             lexeme: String::new(),
                             line: 0,
+                            column: 0,
                         }
                     })
                 },
@@ -735,6 +1042,7 @@ mod tests {
                         t_type: TokenType::String(String::from("true")),
                         lexeme: String::new(),
                         line: 0,
+                        column: 0,
                     }
                 })])),
                 None,
@@ -750,41 +1058,49 @@ mod tests {
                 t_type: TokenType::Identifier("myFunction".to_string()),
                 lexeme: "myFunction".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::LeftParen,
                 lexeme: "(".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Identifier("arg1".to_string()),
                 lexeme: "arg1".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Comma,
                 lexeme: ",".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Identifier("arg2".to_string()),
                 lexeme: "arg2".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::RightParen,
                 lexeme: ")".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::SemiColon,
                 lexeme: ";".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::EOF,
                 lexeme: "".to_string(),
                 line: 1,
+                column: 0,
             },
         ];
 
@@ -832,61 +1148,73 @@ mod tests {
                 t_type: TokenType::Identifier("myFunction".to_string()),
                 lexeme: "myFunction".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::LeftParen,
                 lexeme: "(".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Number(2.0),
                 lexeme: "2".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Star,
                 lexeme: "*".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Identifier("x".to_string()),
                 lexeme: "x".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Comma,
                 lexeme: ",".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Identifier("y".to_string()),
                 lexeme: "y".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Plus,
                 lexeme: "+".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Number(3.0),
                 lexeme: "3".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::RightParen,
                 lexeme: ")".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::SemiColon,
                 lexeme: ";".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::EOF,
                 lexeme: "".to_string(),
                 line: 1,
+                column: 0,
             },
         ];
 
@@ -964,52 +1292,62 @@ mod tests {
                 t_type: TokenType::Fun,
                 lexeme: "fun".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Identifier("myFunction".to_string()),
                 lexeme: "myFunction".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::LeftParen,
                 lexeme: "(".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Identifier("param1".to_string()),
                 lexeme: "param1".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Comma,
                 lexeme: ",".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::Identifier("param2".to_string()),
                 lexeme: "param2".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::RightParen,
                 lexeme: ")".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::LeftBrace,
                 lexeme: "{".to_string(),
                 line: 1,
+                column: 0,
             },
             // Omitted tokens representing the function body
             Token {
                 t_type: TokenType::RightBrace,
                 lexeme: "}".to_string(),
                 line: 1,
+                column: 0,
             },
             Token {
                 t_type: TokenType::EOF,
                 lexeme: "".to_string(),
                 line: 1,
+                column: 0,
             },
         ];
 
@@ -1020,7 +1358,7 @@ mod tests {
 
         // Verify the statement is a function declaration with the correct name, parameters, and body
         match statement {
-            Stmt::Function(name, params, body) => {
+            Stmt::Function(name, params, body, _) => {
                 assert_eq!(name.lexeme, "myFunction");
                 assert_eq!(params.len(), 2);
                 assert_eq!(params[0].lexeme, "param1");
@@ -1031,4 +1369,55 @@ mod tests {
             _ => panic!("Unexpected statement type"),
         }
     }
+
+    #[test]
+    fn test_doc_comment_attached_to_function() {
+        let source = "/// first line\n/// second line\nfun f() {}\nvar x = 1;".as_bytes();
+        let tokens = crate::scanner::Scanner::new(source).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        match &statements[0] {
+            Stmt::Function(_, _, _, doc) => {
+                assert_eq!(doc.as_deref(), Some("first line\nsecond line"));
+            }
+            _ => panic!("Unexpected statement type"),
+        }
+        assert!(std::matches!(statements[1], Stmt::VarDeclaration(..)));
+    }
+
+    #[test]
+    fn test_test_block_is_contextual() {
+        let source = "var test = 1;\ntest \"adds\" { print test; }".as_bytes();
+        let tokens = crate::scanner::Scanner::new(source).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        assert!(std::matches!(statements[0], Stmt::VarDeclaration(..)));
+        match &statements[1] {
+            Stmt::Test(name, body) => {
+                assert_eq!(name.lexeme, "\"adds\"");
+                assert_eq!(body.len(), 1);
+            }
+            _ => panic!("Unexpected statement type"),
+        }
+    }
+
+    #[test]
+    fn test_import_forms() {
+        let source = "import \"a.lox\";\nimport \"b.lox\" as b;\nimport { x, y } from \"c.lox\";"
+            .as_bytes();
+        let tokens = crate::scanner::Scanner::new(source).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        match &statements[..] {
+            [Stmt::Import(a, None, a_names), Stmt::Import(_, Some(alias), b_names), Stmt::Import(_, None, names)] =>
+            {
+                assert_eq!(a.t_type, TokenType::String("a.lox".to_string()));
+                assert!(a_names.is_empty() && b_names.is_empty());
+                assert_eq!(alias.lexeme, "b");
+                let names: Vec<&str> = names.iter().map(|n| n.lexeme.as_str()).collect();
+                assert_eq!(names, ["x", "y"]);
+            }
+            other => panic!("Unexpected statements {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,108 @@
+//Content-Length framed JSON messages over stdio, the transport the DAP and LSP servers share.
+//Messages are plain Lox values, objects are maps and arrays are lists.
+use crate::interpreter::Types;
+use crate::map::Map;
+use crate::stdlib::json;
+use std::cell::RefCell;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+//the next message from the client, `None` once it has hung up
+pub fn read(input: &mut impl BufRead) -> Option<Types> {
+    loop {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            if input.read_line(&mut header).ok()? == 0 {
+                return None;
+            }
+            match header.trim() {
+                "" if length.is_some() => break,
+                "" => {}
+                header => {
+                    if let Some(value) = header.strip_prefix("Content-Length:") {
+                        length = value.trim().parse::<usize>().ok();
+                    }
+                }
+            }
+        }
+        let mut body = vec![0; length?];
+        input.read_exact(&mut body).ok()?;
+        // a message that is not JSON cannot be answered, wait for the next one
+        if let Ok(message) = json::parse(&String::from_utf8_lossy(&body)) {
+            return Some(message);
+        }
+    }
+}
+
+pub fn write(out: &mut impl Write, message: &Types) {
+    let body = json::stringify(message, None).unwrap_or_default();
+    let _ = write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let _ = out.flush();
+}
+
+pub fn string(text: &str) -> Types {
+    Types::ReturnString(text.to_string())
+}
+
+pub fn object(fields: Vec<(&str, Types)>) -> Types {
+    let mut map = Map::new();
+    for (key, value) in fields {
+        map.insert(key.to_string(), value);
+    }
+    Types::Map(Rc::new(RefCell::new(map)))
+}
+
+pub fn list(items: Vec<Types>) -> Types {
+    Types::List(Rc::new(RefCell::new(items)))
+}
+
+pub fn field(message: &Types, key: &str) -> Option<Types> {
+    match message {
+        Types::Map(map) => map.borrow().get(key).cloned(),
+        _ => None,
+    }
+}
+
+pub fn text_field(message: &Types, key: &str) -> Option<String> {
+    match field(message, key) {
+        Some(Types::ReturnString(text)) => Some(text),
+        _ => None,
+    }
+}
+
+pub fn int_field(message: &Types, key: &str) -> Option<i64> {
+    match field(message, key) {
+        Some(Types::Int(n)) => Some(n),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_skips_headers_and_bad_bodies() {
+        let input = "Content-Type: x\r\nContent-Length: 5\r\n\r\n{oops\
+                     Content-Length: 10\r\n\r\n{\"id\": 1}\n";
+        let mut input = Cursor::new(input.as_bytes());
+        let message = read(&mut input).unwrap();
+        assert_eq!(int_field(&message, "id"), Some(1));
+        assert!(read(&mut input).is_none());
+    }
+
+    #[test]
+    fn test_write_frames_the_body() {
+        let mut out = Vec::new();
+        write(
+            &mut out,
+            &object(vec![("id", Types::Int(1)), ("ok", Types::Boolean(true))]),
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Content-Length: 18\r\n\r\n{\"id\":1,\"ok\":true}"
+        );
+    }
+}
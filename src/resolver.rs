@@ -1,7 +1,7 @@
 use crate::ast::{Expr, Stmt, Visitor};
 use crate::error::{parse_error, Error};
 use crate::interpreter::Interpreter;
-use crate::report;
+use crate::module;
 use crate::token::Token;
 use std::collections::HashMap;
 
@@ -11,11 +11,49 @@ pub enum FunctionType {
     Function,
 }
 
+//what introduced a name, editors show it when hovering over the name
+#[derive(Debug, Clone, PartialEq)]
+pub enum BindingKind {
+    Variable,
+    Parameter,
+    Function {
+        params: Vec<String>,
+        doc: Option<String>,
+    },
+    Class {
+        doc: Option<String>,
+    },
+    Import,
+}
+
+//A declared name and the places it is read or assigned, kept for editor tooling.
+//Uses no scope declares are matched to the top level names once everything is resolved.
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub name: Token,
+    pub kind: BindingKind,
+    // how many scopes in it was declared, 0 for the top level
+    pub depth: usize,
+    pub references: Vec<Token>,
+}
+
+#[derive(Debug)]
+struct Local {
+    defined: bool,
+    // index into `bindings`
+    binding: usize,
+}
+
 #[derive(Debug)]
 pub struct Resolver<'a> {
     interpreter: &'a mut Interpreter,
-    scopes: Vec<HashMap<String, bool>>,
+    scopes: Vec<HashMap<String, Local>>,
     current_function: FunctionType,
+    had_error: bool,
+    bindings: Vec<Binding>,
+    // the first top level declaration of each name, names no scope has are looked up here
+    top_level: HashMap<String, usize>,
+    unresolved: Vec<Token>,
 }
 
 impl<'a> Resolver<'a> {
@@ -24,7 +62,26 @@ impl<'a> Resolver<'a> {
             interpreter,
             scopes: Vec::new(),
             current_function: FunctionType::None,
+            had_error: false,
+            bindings: Vec::new(),
+            top_level: HashMap::new(),
+            unresolved: Vec::new(),
+        }
+    }
+
+    //every name declared in the resolved statements, in declaration order
+    pub fn into_bindings(mut self) -> Vec<Binding> {
+        for name in std::mem::take(&mut self.unresolved) {
+            if let Some(&index) = self.top_level.get(&name.lexeme) {
+                self.bindings[index].references.push(name);
+            }
         }
+        self.bindings
+    }
+
+    //whether a static error was reported, the program should not be run
+    pub fn had_error(&self) -> bool {
+        self.had_error
     }
 
     fn resolve_stmt(&mut self, statement: &Stmt) {
@@ -48,22 +105,34 @@ impl<'a> Resolver<'a> {
         self.scopes.pop();
     }
 
-    fn declare(&mut self, name: &Token) {
+    fn declare(&mut self, name: &Token, kind: BindingKind) {
+        let binding = self.bindings.len();
+        self.bindings.push(Binding {
+            name: name.clone(),
+            kind,
+            depth: self.scopes.len(),
+            references: Vec::new(),
+        });
         match self.scopes.last_mut() {
             Some(ref mut scope) => {
                 if scope.contains_key(&name.lexeme) {
-                    parse_error(name, "Already a variable with this name in this scope.")
+                    parse_error(name, "Already a variable with this name in this scope.");
+                    self.had_error = true;
                 }
-                scope.insert(name.lexeme.clone(), false);
+                scope.insert(name.lexeme.clone(), Local { defined: false, binding });
+            }
+            None => {
+                self.top_level.entry(name.lexeme.clone()).or_insert(binding);
             }
-            None => (),
         }
     }
 
     fn define(&mut self, name: &Token) {
         match self.scopes.last_mut() {
             Some(ref mut scope) => {
-                scope.insert(name.lexeme.clone(), true);
+                if let Some(local) = scope.get_mut(&name.lexeme) {
+                    local.defined = true;
+                }
             }
             None => (),
         }
@@ -71,10 +140,13 @@ impl<'a> Resolver<'a> {
 
     fn resolve_local(&mut self, name: &Token) {
         for (i, scope) in self.scopes.iter().rev().enumerate() {
-            if scope.contains_key(&name.lexeme) {
+            if let Some(local) = scope.get(&name.lexeme) {
+                self.bindings[local.binding].references.push(name.clone());
                 self.interpreter.resolve(name, i);
+                return;
             }
         }
+        self.unresolved.push(name.clone());
     }
 
     fn resolve_function(
@@ -86,7 +158,7 @@ impl<'a> Resolver<'a> {
         self.current_function = function_tpe;
         self.begin_scope();
         for param in params {
-            self.declare(param);
+            self.declare(param, BindingKind::Parameter);
             self.define(param);
         }
 
@@ -108,6 +180,15 @@ impl<'a> Visitor for Resolver<'_> {
                 self.resolve_local(name);
                 Ok(())
             }
+            &Expr::CompoundAssign {
+                ref name,
+                ref value,
+                ..
+            } => {
+                self.resolve_expr(value);
+                self.resolve_local(name);
+                Ok(())
+            }
             &Expr::Binary {
                 ref left,
                 ref op,
@@ -128,7 +209,21 @@ impl<'a> Visitor for Resolver<'_> {
                 }
                 Ok(())
             }
-            &Expr::Grouping { ref expr } => {
+            &Expr::Get { ref object, .. } => {
+                self.resolve_expr(object);
+                Ok(())
+            }
+            &Expr::Ternary {
+                ref condition,
+                ref then_branch,
+                ref else_branch,
+            } => {
+                self.resolve_expr(condition);
+                self.resolve_expr(then_branch);
+                self.resolve_expr(else_branch);
+                Ok(())
+            }
+            &Expr::Grouping { ref expr } | &Expr::OptionalChain { ref expr } => {
                 self.resolve_expr(expr);
                 Ok(())
             }
@@ -148,13 +243,10 @@ impl<'a> Visitor for Resolver<'_> {
             }
             &Expr::Variable { ref name } => {
                 if let Some(scopes) = self.scopes.last_mut() {
-                    if let Some(flag) = scopes.get(&name.lexeme) {
-                        if flag == &false {
-                            report(
-                                name.clone().line,
-                                &format!(" at '{}'", name.lexeme),
-                                "Can't read local variable in its own initializer.",
-                            )
+                    if let Some(local) = scopes.get(&name.lexeme) {
+                        if !local.defined {
+                            parse_error(name, "Can't read local variable in its own initializer.");
+                            self.had_error = true;
                         }
                     }
                 };
@@ -173,8 +265,8 @@ impl<'a> Visitor for Resolver<'_> {
                 self.end_scope();
                 Ok(())
             }
-            &Stmt::Class(ref token, ref stmt) => {
-                self.declare(token);
+            &Stmt::Class(ref token, ref stmt, ref doc) => {
+                self.declare(token, BindingKind::Class { doc: doc.clone() });
                 self.define(token);
                 Ok(())
             }
@@ -182,9 +274,28 @@ impl<'a> Visitor for Resolver<'_> {
                 self.resolve_expr(expr);
                 Ok(())
             }
+            &Stmt::For(ref initializer, ref condition, ref increment, ref body) => {
+                self.begin_scope();
+                if let Some(init) = initializer {
+                    self.resolve_stmt(init);
+                }
+                if let Some(cond) = condition {
+                    self.resolve_expr(cond);
+                }
+                if let Some(inc) = increment {
+                    self.resolve_expr(inc);
+                }
+                self.resolve_stmt(body);
+                self.end_scope();
+                Ok(())
+            }
 
-            &Stmt::Function(ref name, ref params, ref body) => {
-                self.declare(name);
+            &Stmt::Function(ref name, ref params, ref body, ref doc) => {
+                let kind = BindingKind::Function {
+                    params: params.iter().map(|param| param.lexeme.clone()).collect(),
+                    doc: doc.clone(),
+                };
+                self.declare(name, kind);
                 self.define(name);
                 self.resolve_function(params, body, FunctionType::Function);
                 Ok(())
@@ -197,21 +308,62 @@ impl<'a> Visitor for Resolver<'_> {
                 }
                 Ok(())
             }
+            &Stmt::Import(ref path, ref alias, ref names) => {
+                if names.is_empty() {
+                    let name = alias.clone().unwrap_or_else(|| module::binding_name(path));
+                    self.declare(&name, BindingKind::Import);
+                    self.define(&name);
+                }
+                for name in names {
+                    self.declare(name, BindingKind::Import);
+                    self.define(name);
+                }
+                Ok(())
+            }
             &Stmt::Print(ref expr) => {
                 self.resolve_expr(expr);
                 Ok(())
             }
             &Stmt::Return(ref keyword, ref value) => {
                 if let FunctionType::None = &self.current_function {
-                    parse_error(keyword, "Cannot return from top-level code.")
+                    parse_error(keyword, "Cannot return from top-level code.");
+                    self.had_error = true;
                 }
                 if let Some(keyword_value) = value {
                     self.resolve_expr(keyword_value);
                 };
                 Ok(())
             }
+            &Stmt::Test(_, ref body) => {
+                self.begin_scope();
+                self.resolve_stmts(body);
+                self.end_scope();
+                Ok(())
+            }
+            &Stmt::Throw(_, ref value) => {
+                self.resolve_expr(value);
+                Ok(())
+            }
+            &Stmt::Try(_, ref body, ref catch, ref finally) => {
+                self.begin_scope();
+                self.resolve_stmts(body);
+                self.end_scope();
+                if let Some((name, handler)) = catch {
+                    self.begin_scope();
+                    self.declare(name, BindingKind::Variable);
+                    self.define(name);
+                    self.resolve_stmts(handler);
+                    self.end_scope();
+                }
+                if let Some(finally) = finally {
+                    self.begin_scope();
+                    self.resolve_stmts(finally);
+                    self.end_scope();
+                }
+                Ok(())
+            }
             &Stmt::VarDeclaration(ref name, ref initializer) => {
-                self.declare(name);
+                self.declare(name, BindingKind::Variable);
                 if let Some(init) = initializer {
                     self.resolve_expr(init)
                 }
@@ -226,3 +378,48 @@ impl<'a> Visitor for Resolver<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn bindings(source: &str) -> Vec<Binding> {
+        let statements = Parser::new(Scanner::new(source.as_bytes()).scan_tokens())
+            .parse()
+            .unwrap();
+        let mut interpreter = Interpreter::new();
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve_stmts(&statements);
+        resolver.into_bindings()
+    }
+
+    fn uses(binding: &Binding) -> Vec<(u32, u32)> {
+        binding.references.iter().map(|name| (name.line, name.column)).collect()
+    }
+
+    #[test]
+    fn test_bindings_record_their_uses() {
+        let found = bindings(
+            "fun twice(n) {\n  var doubled = n + n;\n  return doubled + total;\n}\nvar total = twice(1);\n",
+        );
+        let names: Vec<(&str, usize)> = found
+            .iter()
+            .map(|binding| (binding.name.lexeme.as_str(), binding.depth))
+            .collect();
+        assert_eq!(names, [("twice", 0), ("n", 1), ("doubled", 1), ("total", 0)]);
+        assert_eq!(
+            found[0].kind,
+            BindingKind::Function {
+                params: vec!["n".to_string()],
+                doc: None
+            }
+        );
+        assert_eq!(uses(&found[1]), [(2, 17), (2, 21)]);
+        assert_eq!(uses(&found[2]), [(3, 10)]);
+        // used in `twice` before it is declared, the use is found once resolving is done
+        assert_eq!(uses(&found[3]), [(3, 20)]);
+        assert_eq!(uses(&found[0]), [(5, 13)]);
+    }
+}
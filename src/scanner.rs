@@ -1,7 +1,6 @@
 use crate::error::error;
-use crate::report;
 use crate::token::TokenType;
-use crate::token::Token;
+use crate::token::{Comment, Token};
 
 //TODO ADD BETTER ERROR HANDLING
 //Takes in bytes as input ands tokenizes it
@@ -11,6 +10,15 @@ pub struct Scanner<'a>{
     start: usize,
     current: usize,
     line: usize,
+    // where the current line and the token being scanned start, for columns
+    line_start: usize,
+    column: usize,
+    // comments are kept as trivia so the formatter can put them back
+    comments: Vec<Comment>,
+    // every `{` gets the next id, comments remember the innermost open one
+    blocks: Vec<usize>,
+    block_count: usize,
+    had_error: bool,
 }
 
 impl <'a> Scanner<'a> {
@@ -20,25 +28,43 @@ impl <'a> Scanner<'a> {
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            column: 1,
+            comments: Vec::new(),
+            blocks: Vec::new(),
+            block_count: 0,
+            had_error: false,
         }
     }
 
+    //whether any character could not be scanned
+    pub fn had_error(&self) -> bool {
+        self.had_error
+    }
+
+    //returns the comments skipped while scanning, in source order
+    pub fn take_comments(&mut self) -> Vec<Comment> {
+        std::mem::take(&mut self.comments)
+    }
+
     //return a vector of tokens
     pub fn scan_tokens(&mut self) -> Vec<Token> {
         let mut vec_tokens: Vec<Token> = vec![];
 
         while !self.at_end() {
             self.start = self.current;
+            self.column = self.start - self.line_start + 1;
             match self.scan_token() {
                 Ok(Some(token)) => vec_tokens.push(token),
                 Ok(None) => {},
-                Err(()) => {}
+                Err(()) => self.had_error = true,
             };
         }
         vec_tokens.push(Token {
             t_type: TokenType::EOF,
             lexeme: String::new(),
-            line: self.line as u32
+            line: self.line as u32,
+            column: (self.current - self.line_start + 1) as u32,
         }
         );
 
@@ -51,14 +77,61 @@ impl <'a> Scanner<'a> {
         match c {
             b'(' => Ok(Some(self.add_token(TokenType::LeftParen))),
             b')' => Ok(Some(self.add_token(TokenType::RightParen))),
-            b'{' => Ok(Some(self.add_token(TokenType::LeftBrace))),
-            b'}' => Ok(Some(self.add_token(TokenType::RightBrace))),
+            b'{' => {
+                self.block_count += 1;
+                self.blocks.push(self.block_count);
+                Ok(Some(self.add_token(TokenType::LeftBrace)))
+            }
+            b'}' => {
+                self.blocks.pop();
+                Ok(Some(self.add_token(TokenType::RightBrace)))
+            }
             b',' => Ok(Some(self.add_token(TokenType::Comma))),
             b'.' => Ok(Some(self.add_token(TokenType::Dot))),
-            b'-' => Ok(Some(self.add_token(TokenType::Minus))),
-            b'+' => Ok(Some(self.add_token(TokenType::Plus))),
+            b'-' => {
+                Ok(if self.match_by(b'=') {
+                    Some(self.add_token(TokenType::MinusEqual))
+                } else {
+                    Some(self.add_token(TokenType::Minus))
+                })
+            }
+            b'+' => {
+                Ok(if self.match_by(b'=') {
+                    Some(self.add_token(TokenType::PlusEqual))
+                } else {
+                    Some(self.add_token(TokenType::Plus))
+                })
+            }
             b';' => Ok(Some(self.add_token(TokenType::SemiColon))),
-            b'*' => Ok(Some(self.add_token(TokenType::Star))),
+            b'*' => {
+                Ok(if self.match_by(b'*') {
+                    Some(self.add_token(TokenType::StarStar))
+                } else if self.match_by(b'=') {
+                    Some(self.add_token(TokenType::StarEqual))
+                } else {
+                    Some(self.add_token(TokenType::Star))
+                })
+            }
+            b'%' => {
+                Ok(if self.match_by(b'=') {
+                    Some(self.add_token(TokenType::PercentEqual))
+                } else {
+                    Some(self.add_token(TokenType::Percent))
+                })
+            }
+            b'&' => Ok(Some(self.add_token(TokenType::Ampersand))),
+            b'|' => Ok(Some(self.add_token(TokenType::Pipe))),
+            b'^' => Ok(Some(self.add_token(TokenType::Caret))),
+            b'~' => Ok(Some(self.add_token(TokenType::Tilde))),
+            b'?' => {
+                Ok(if self.match_by(b'?') {
+                    Some(self.add_token(TokenType::QuestionQuestion))
+                } else if self.match_by(b'.') {
+                    Some(self.add_token(TokenType::QuestionDot))
+                } else {
+                    Some(self.add_token(TokenType::Question))
+                })
+            }
             b':' => Ok(Some(self.add_token(TokenType::Colon))),
             b'!' => {
                 Ok(if self.match_by(b'=') {
@@ -77,6 +150,8 @@ impl <'a> Scanner<'a> {
             b'<' => {
                 Ok(if self.match_by(b'='){
                     Some(self.add_token(TokenType::LessEqual))
+                }else if self.match_by(b'<') {
+                    Some(self.add_token(TokenType::LessLess))
                 }else {
                     Some(self.add_token(TokenType::Less))
                 })
@@ -84,19 +159,22 @@ impl <'a> Scanner<'a> {
             b'>' => {
                 Ok(if self.match_by(b'='){
                     Some(self.add_token(TokenType::GreaterEqual))
+                }else if self.match_by(b'>') {
+                    Some(self.add_token(TokenType::GreaterGreater))
                 }else{
                    Some( self.add_token(TokenType::Greater))
                 })
             }
             b'/' => {
-                Ok(if self.match_by(b'/'){
-                    while self.peek() != b'\n' && self.peek() != b'\0'{
-                        self.advance();
-                    }
-                    None
-                }else {
-                    Some(self.add_token(TokenType::Slash))
-                })
+                if self.match_by(b'/') {
+                    self.line_comment()
+                } else if self.match_by(b'*') {
+                    self.block_comment()
+                } else if self.match_by(b'=') {
+                    Ok(Some(self.add_token(TokenType::SlashEqual)))
+                } else {
+                    Ok(Some(self.add_token(TokenType::Slash)))
+                }
             }
 
             b' ' | b'\t' | b'\r' => {
@@ -105,6 +183,7 @@ impl <'a> Scanner<'a> {
 
             b'\n' => {
                 self.line += 1;
+                self.line_start = self.current;
                 Ok(None)
             }
 
@@ -122,11 +201,13 @@ impl <'a> Scanner<'a> {
                     match ident.as_str() {
                         "and" => Ok(Some(self.add_token(TokenType::And))),
                         "break" => Ok(Some(self.add_token(TokenType::Break))),
+                        "catch" => Ok(Some(self.add_token(TokenType::Catch))),
                         "class" => Ok(Some(self.add_token(TokenType::Class))),
                         "else" => Ok(Some(self.add_token(TokenType::Else))),
                         "false" => Ok(Some(self.add_token(TokenType::False))),
                         "fun" => Ok(Some(self.add_token(TokenType::Fun))),
                         "for" => Ok(Some(self.add_token(TokenType::For))),
+                        "import" => Ok(Some(self.add_token(TokenType::Import))),
                         "if" => Ok(Some(self.add_token(TokenType::If))),
                         "nil" => Ok(Some(self.add_token(TokenType::Nil))),
                         "or" => Ok(Some(self.add_token(TokenType::Or))),
@@ -137,10 +218,13 @@ impl <'a> Scanner<'a> {
                         "true" => Ok(Some(self.add_token(TokenType::True))),
                         "var" => Ok(Some(self.add_token(TokenType::Var))),
                         "while" => Ok(Some(self.add_token(TokenType::While))),
+                        "throw" => Ok(Some(self.add_token(TokenType::Throw))),
+                        "try" => Ok(Some(self.add_token(TokenType::Try))),
+                        "finally" => Ok(Some(self.add_token(TokenType::Finally))),
                          _ => Ok(Some(self.add_token(TokenType::Identifier(ident))))
                     }
 
-                }else { let _ = report(self.line as u32, "at the", "Unexpected char");  Err(()) };
+                }else { error(self.line as u32, self.column as u32, "Unexpected character."); Err(()) };
                 return c;
             }
         }
@@ -151,6 +235,7 @@ impl <'a> Scanner<'a> {
             t_type,
             lexeme: self.sub_string(self.start, self.current).unwrap(),
             line: self.line as u32,
+            column: self.column as u32,
         }
     }
 
@@ -196,22 +281,75 @@ impl <'a> Scanner<'a> {
     //returns a substring or returns an error
     fn sub_string(&self, start: usize, current: usize) -> Result<String, ()> {
         return String::from_utf8(self.source[start..current].to_vec()).map_err(|_| {
-            error(self.line as u32, "Unexpected character.");
+            error(self.line as u32, self.column as u32, "Unexpected character.");
         })
     }
 
+    //keeps a `//` comment as trivia, `///` doc comments also become a token for the parser
+    fn line_comment(&mut self) -> Result<Option<Token>, ()> {
+        let doc = self.peek() == b'/' && self.peek_next() != b'/';
+        while self.peek() != b'\n' && self.peek() != b'\0'{
+            self.advance();
+        }
+        let text = self.sub_string(self.start, self.current)?;
+        self.add_comment(self.line, text.trim_end().to_string());
+        if doc {
+            let doc_text = text[3..].trim().to_string();
+            return Ok(Some(self.add_token(TokenType::DocComment(doc_text))));
+        }
+        Ok(None)
+    }
+
+    //skips a `/* ... */` comment, they nest so `/* /* */ */` is a single comment
+    fn block_comment(&mut self) -> Result<Option<Token>, ()> {
+        let start_line = self.line;
+        let mut depth = 1;
+        while depth > 0 {
+            if self.at_end() {
+                error(start_line as u32, self.column as u32, "Unterminated block comment.");
+                return Err(());
+            }
+            if self.peek() == b'/' && self.peek_next() == b'*' {
+                self.current += 2;
+                depth += 1;
+            } else if self.peek() == b'*' && self.peek_next() == b'/' {
+                self.current += 2;
+                depth -= 1;
+            } else {
+                if self.peek() == b'\n' {
+                    self.line += 1;
+                    self.line_start = self.current + 1;
+                }
+                self.advance();
+            }
+        }
+        let text = self.sub_string(self.start, self.current)?;
+        self.add_comment(start_line, text);
+        Ok(None)
+    }
+
+    fn add_comment(&mut self, line: usize, text: String) {
+        self.comments.push(Comment {
+            line: line as u32,
+            block: self.blocks.last().copied().unwrap_or(0),
+            text,
+        });
+    }
+
     // returns whether its a string literal idk whats that called or throws an error
     fn string(&mut self) -> Result<Option<Token>, ()> {
+        let start_line = self.line;
         while self.peek() != b'"'  && !self.at_end(){
             if self.peek()==b'\n'{
-                self.line += 1
+                self.line += 1;
+                self.line_start = self.current + 1;
             }
             self.advance();
 
         }
         if self.at_end(){
-            error(self.line as u32, "Unterminated string");
-            ()
+            error(start_line as u32, self.column as u32, "Unterminated string.");
+            return Err(());
         }
         self.advance();
         let string = self.sub_string(self.start+1, self.current-1)?;
@@ -221,25 +359,95 @@ impl <'a> Scanner<'a> {
     fn is_digit(&self, c:u8 ) -> bool {
         return c.is_ascii_digit()
     }
-    //return an number literal
+    //return an number literal, also accepts 0x1F, 0b1010, 1_000 and 1.5e-3
+    //literals without a fraction or exponent are integers
     fn number(&mut self) ->Result<Option<Token>, ()>{
-        while self.peek().is_ascii_digit(){
-            self.advance();
+        let radix = match (self.source[self.start], self.peek()) {
+            (b'0', b'x') | (b'0', b'X') => Some((16, "hex")),
+            (b'0', b'b') | (b'0', b'B') => Some((2, "binary")),
+            _ => None,
+        };
+        if let Some((radix, name)) = radix {
+            return self.radix_number(radix, name);
         }
+
+        let mut is_float = false;
+        self.digits(|c| c.is_ascii_digit())?;
         if self.peek() == b'.' && self.peek_next().is_ascii_digit(){
+            is_float = true;
+            self.advance();
+            self.digits(|c| c.is_ascii_digit())?;
+        }
+        if self.peek() == b'e' || self.peek() == b'E' {
+            is_float = true;
             self.advance();
-            while self.peek().is_ascii_digit(){
+            if self.peek() == b'+' || self.peek() == b'-' {
                 self.advance();
             }
+            if !self.peek().is_ascii_digit() {
+                return self.number_error("expected digits in the exponent");
+            }
+            self.digits(|c| c.is_ascii_digit())?;
         }
 
-        let num: f64 = self.sub_string(self.start, self.current)?.parse::<f64>().expect("Weird, I'm super sure this ought to be a valid f64");
+        let text = self.sub_string(self.start, self.current)?.replace('_', "");
+        if !is_float {
+            return match text.parse::<i64>() {
+                Ok(value) => Ok(Some(self.add_token(TokenType::Int(value)))),
+                Err(_) => self.number_error("integer literal is too large"),
+            };
+        }
+        let num: f64 = text.parse::<f64>().expect("Weird, I'm super sure this ought to be a valid f64");
 
         Ok(Some(self.add_token(TokenType::Number(num))))
     }
+
+    //a `0x` or `0b` literal, always an integer
+    fn radix_number(&mut self, radix: u32, name: &str) -> Result<Option<Token>, ()> {
+        self.advance();
+        if !self.peek().is_ascii_digit() && !self.peek().is_ascii_alphabetic() {
+            return self.number_error(&format!("expected {} digits after the prefix", name));
+        }
+        self.digits(|c| c.is_ascii_alphanumeric())?;
+
+        let text = self.sub_string(self.start + 2, self.current)?.replace('_', "");
+        if let Some(bad) = text.chars().find(|c| !c.is_digit(radix)) {
+            return self.number_error(&format!("invalid digit '{}' in {} literal", bad, name));
+        }
+        match i64::from_str_radix(&text, radix) {
+            Ok(value) => Ok(Some(self.add_token(TokenType::Int(value)))),
+            Err(_) => self.number_error(&format!("{} literal is too large", name)),
+        }
+    }
+
+    //consumes a run of digits, `_` is only allowed between two of them
+    fn digits(&mut self, is_digit: fn(u8) -> bool) -> Result<(), ()> {
+        while is_digit(self.peek()) || self.peek() == b'_' {
+            if self.peek() == b'_' {
+                let after_digit = is_digit(self.source[self.current - 1]);
+                if !after_digit || !is_digit(self.peek_next()) {
+                    self.advance();
+                    return self.number_error("'_' must be placed between two digits").map(|_| ());
+                }
+            }
+            self.advance();
+        }
+        Ok(())
+    }
+
+    fn number_error(&mut self, message: &str) -> Result<Option<Token>, ()> {
+        // skip the rest of the literal so it does not turn into more tokens
+        while self.peek().is_ascii_alphanumeric() || self.peek() == b'_' {
+            self.advance();
+        }
+        let literal = self.sub_string(self.start, self.current)?;
+        error(self.line as u32, self.column as u32, &format!("Invalid number '{}': {}.", literal, message));
+        Err(())
+    }
+
     //return an identifier from reading a text file
     fn identifier(&mut self) -> String {
-        while self.peek().is_ascii_alphanumeric()  {
+        while self.peek().is_ascii_alphanumeric() || self.peek() == b'_' {
             self.advance();
         }
         let ident: String = self.sub_string(self.start, self.current).expect("invalid character").parse::<String>().expect("Unable to parse the identifier or Invalid identifier");
@@ -251,6 +459,7 @@ impl <'a> Scanner<'a> {
 #[cfg(test)]
 mod tests {
     use crate::scanner::Scanner;
+    use crate::token::{Token, TokenType};
     use String;
 
     #[test]
@@ -265,4 +474,98 @@ mod tests {
         println!("{:?}", x);
         assert_eq!(true, true);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn check_nested_block_comment(){
+        let source = String::from("1 /* outer /* inner */\n still */ 2 \n 3").into_bytes();
+        let mut sc = Scanner::new(&source);
+        let tokens = sc.scan_tokens();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[1].t_type, TokenType::Int(2));
+        assert_eq!(tokens[1].line, 2);
+        assert_eq!(tokens[2].line, 3);
+        assert_eq!(sc.take_comments()[0].text, "/* outer /* inner */\n still */");
+    }
+
+    #[test]
+    fn check_unterminated_block_comment(){
+        let source = String::from("1 /* /* */").into_bytes();
+        let mut sc = Scanner::new(&source);
+        sc.scan_tokens();
+
+        assert!(sc.had_error());
+    }
+
+    #[test]
+    fn check_doc_comment(){
+        let source = String::from("/// adds one\n//// not a doc\nfun").into_bytes();
+        let mut sc = Scanner::new(&source);
+        let tokens = sc.scan_tokens();
+
+        assert_eq!(tokens[0].t_type, TokenType::DocComment("adds one".to_string()));
+        assert_eq!(tokens[1].t_type, TokenType::Fun);
+        assert_eq!(sc.take_comments().len(), 2);
+    }
+
+    #[test]
+    fn check_columns(){
+        let (tokens, _) = scan_one("var a = 1;\n  print \"x\" + a;");
+        let columns: Vec<(u32, u32)> = tokens.iter().map(|t| (t.line, t.column)).collect();
+        assert_eq!(
+            columns,
+            [(1, 1), (1, 5), (1, 7), (1, 9), (1, 10), (2, 3), (2, 9), (2, 13), (2, 15), (2, 16), (2, 17)]
+        );
+    }
+
+    fn scan_one(source: &str) -> (Vec<Token>, bool) {
+        let binding = String::from(source).into_bytes();
+        let mut sc = Scanner::new(&binding);
+        let tokens = sc.scan_tokens();
+        (tokens, sc.had_error())
+    }
+
+    #[test]
+    fn check_number_literals(){
+        for (source, value) in [
+            ("0x1F", TokenType::Int(31)),
+            ("0XfF", TokenType::Int(255)),
+            ("0b1010", TokenType::Int(10)),
+            ("1_000_000", TokenType::Int(1000000)),
+            ("0xFF_FF", TokenType::Int(65535)),
+            ("9007199254740993", TokenType::Int(9007199254740993)),
+            ("2.5", TokenType::Number(2.5)),
+            ("1.5e-3", TokenType::Number(0.0015)),
+            ("2E+2", TokenType::Number(200.0)),
+            ("1_0.2_5e1_0", TokenType::Number(10.25e10)),
+        ] {
+            let (tokens, had_error) = scan_one(source);
+            assert!(!had_error, "{}", source);
+            assert_eq!(tokens[0].t_type, value, "{}", source);
+            assert_eq!(tokens[0].lexeme, source);
+        }
+    }
+
+    #[test]
+    fn check_malformed_number_literals(){
+        for source in ["0x", "0b", "0b102", "0xG", "1e", "1e+", "1__0", "1_", "0x_1", "9223372036854775808"] {
+            let (tokens, had_error) = scan_one(source);
+            assert!(had_error, "{}", source);
+            assert_eq!(tokens.len(), 1, "{}", source);
+        }
+    }
+
+    #[test]
+    fn check_operator_tokens(){
+        let (tokens, _) = scan_one("** *= += -= /= %= << >> & | ^ ~ <= >= ? ?? ?.");
+        let types: Vec<TokenType> = tokens.into_iter().map(|t| t.t_type).collect();
+        assert_eq!(types, vec![
+            TokenType::StarStar, TokenType::StarEqual, TokenType::PlusEqual,
+            TokenType::MinusEqual, TokenType::SlashEqual, TokenType::PercentEqual,
+            TokenType::LessLess, TokenType::GreaterGreater, TokenType::Ampersand,
+            TokenType::Pipe, TokenType::Caret, TokenType::Tilde,
+            TokenType::LessEqual, TokenType::GreaterEqual, TokenType::Question,
+            TokenType::QuestionQuestion, TokenType::QuestionDot, TokenType::EOF,
+        ]);
+    }
+}
@@ -0,0 +1,133 @@
+//creating and reshaping lists and maps
+use crate::environment::Environment;
+use crate::heap;
+use crate::interpreter::{Interpreter, Types};
+use crate::map::Map;
+use crate::stdlib::{created, define, int_arg};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub fn register(globals: &Environment) {
+    define(globals, "list", 0, list);
+    define(globals, "map", 0, map);
+    define(globals, "push", 2, push);
+    define(globals, "get", 2, get);
+    define(globals, "set", 3, set);
+    define(globals, "has", 2, has);
+    define(globals, "remove", 2, remove);
+    define(globals, "keys", 1, keys);
+    define(globals, "values", 1, values);
+}
+
+pub fn new_list(items: Vec<Types>) -> Types {
+    Types::List(Rc::new(RefCell::new(items)))
+}
+
+fn list(_: &mut Interpreter, _: &Vec<Types>) -> Result<Types, String> {
+    Ok(new_list(Vec::new()))
+}
+
+fn map(_: &mut Interpreter, _: &Vec<Types>) -> Result<Types, String> {
+    Ok(Types::Map(Rc::new(RefCell::new(Map::new()))))
+}
+
+fn map_arg<'a>(name: &str, args: &'a [Types], index: usize) -> Result<&'a Rc<RefCell<Map>>, String> {
+    match &args[index] {
+        Types::Map(map) => Ok(map),
+        other => Err(format!(
+            "{} expects a map as argument {}, got {}.",
+            name,
+            index + 1,
+            other
+        )),
+    }
+}
+
+//a position in a list of `len` items, negative indexes count from the end
+fn list_index(name: &str, args: &[Types], len: usize) -> Result<usize, String> {
+    let index = int_arg(name, args, 1)?;
+    let position = if index < 0 { index + len as i64 } else { index };
+    if position < 0 || position >= len as i64 {
+        return Err(format!(
+            "{} index {} is out of range for a list of {} items.",
+            name, index, len
+        ));
+    }
+    Ok(position as usize)
+}
+
+//adds to the end of the list and returns the new length
+fn push(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    match &args[0] {
+        Types::List(items) => {
+            // the returned length is free, the heap limit has to see the new item here
+            interpreter.charge(heap::item_size(&args[1]))?;
+            items.borrow_mut().push(args[1].clone());
+            Ok(Types::Int(items.borrow().len() as i64))
+        }
+        other => Err(format!("push expects a list as argument 1, got {}.", other)),
+    }
+}
+
+//an item of a list by index or the value of a map key, nil when the map has no such key
+//map keys can be any value, they match when `==` would say they are equal
+fn get(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    match &args[0] {
+        Types::List(items) => {
+            let items = items.borrow();
+            Ok(items[list_index("get", args, items.len())?].clone())
+        }
+        Types::Map(map) => {
+            Ok(map.borrow().get_key(&args[1]).cloned().unwrap_or(Types::Nil))
+        }
+        other => Err(format!("get expects a list or a map as argument 1, got {}.", other)),
+    }
+}
+
+//replaces a list item or adds a map key, returns the value
+fn set(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    match &args[0] {
+        Types::List(items) => {
+            let index = list_index("set", args, items.borrow().len())?;
+            interpreter.charge(heap::text_size(&args[2]))?;
+            items.borrow_mut()[index] = args[2].clone();
+        }
+        Types::Map(map) => {
+            let key = &args[1];
+            let bytes = if map.borrow().contains_key(key) {
+                heap::text_size(&args[2])
+            } else {
+                heap::item_size(&args[2]) + heap::text_size(key)
+            };
+            interpreter.charge(bytes)?;
+            map.borrow_mut().insert_key(key.clone(), args[2].clone());
+        }
+        other => {
+            return Err(format!("set expects a list or a map as argument 1, got {}.", other))
+        }
+    }
+    Ok(args[2].clone())
+}
+
+fn has(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let map = map_arg("has", args, 0)?;
+    Ok(Types::Boolean(map.borrow().contains_key(&args[1])))
+}
+
+//removes a map key and returns its value, nil when it was not there
+fn remove(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let map = map_arg("remove", args, 0)?;
+    let removed = map.borrow_mut().remove_key(&args[1]);
+    Ok(removed.unwrap_or(Types::Nil))
+}
+
+fn keys(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let keys = map_arg("keys", args, 0)?.borrow().iter().map(|(key, _)| key.clone()).collect();
+    created(interpreter, new_list(keys))
+}
+
+fn values(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let map = map_arg("values", args, 0)?.borrow();
+    let values = map.iter().map(|(_, value)| value.clone()).collect();
+    created(interpreter, new_list(values))
+}
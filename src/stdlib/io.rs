@@ -0,0 +1,167 @@
+//file and stdin natives, they only run when the interpreter has the fs capability
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, Types};
+use crate::stdlib::{created, define, string_arg};
+use std::cell::RefCell;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+pub fn register(globals: &Environment) {
+    define(globals, "read_line", 0, read_line);
+    define(globals, "read_file", 1, read_file);
+    define(globals, "write_file", 2, write_file);
+    define(globals, "append_file", 2, append_file);
+    define(globals, "file_exists", 1, file_exists);
+    define(globals, "list_dir", 1, list_dir);
+}
+
+fn require_fs(interpreter: &Interpreter, name: &str) -> Result<(), String> {
+    if interpreter.capabilities.fs {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} needs file system access, which is disabled (run with --allow-fs).",
+            name
+        ))
+    }
+}
+
+//the next line without its line ending, nil once the input is exhausted
+fn read_line(interpreter: &mut Interpreter, _: &Vec<Types>) -> Result<Types, String> {
+    require_fs(interpreter, "read_line")?;
+    let mut line = String::new();
+    match interpreter.input().read_line(&mut line) {
+        Ok(0) => Ok(Types::Nil),
+        Ok(_) => {
+            let trimmed = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(trimmed);
+            created(interpreter, Types::ReturnString(line))
+        }
+        Err(e) => Err(format!("read_line failed: {}.", e)),
+    }
+}
+
+fn read_file(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    require_fs(interpreter, "read_file")?;
+    let path = string_arg("read_file", args, 0)?;
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("read_file could not read '{}': {}.", path, e))?;
+    created(interpreter, Types::ReturnString(contents))
+}
+
+fn write_file(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    require_fs(interpreter, "write_file")?;
+    let path = string_arg("write_file", args, 0)?;
+    let contents = string_arg("write_file", args, 1)?;
+    fs::write(path, contents)
+        .map(|_| Types::Nil)
+        .map_err(|e| format!("write_file could not write '{}': {}.", path, e))
+}
+
+//creates the file when it does not exist yet
+fn append_file(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    require_fs(interpreter, "append_file")?;
+    let path = string_arg("append_file", args, 0)?;
+    let contents = string_arg("append_file", args, 1)?;
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map(|_| Types::Nil)
+        .map_err(|e| format!("append_file could not write '{}': {}.", path, e))
+}
+
+fn file_exists(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    require_fs(interpreter, "file_exists")?;
+    let path = string_arg("file_exists", args, 0)?;
+    Ok(Types::Boolean(Path::new(path).exists()))
+}
+
+//names of the entries in a directory, sorted
+fn list_dir(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    require_fs(interpreter, "list_dir")?;
+    let path = string_arg("list_dir", args, 0)?;
+    let error = |e: std::io::Error| format!("list_dir could not read '{}': {}.", path, e);
+    let mut names = Vec::new();
+    for entry in fs::read_dir(path).map_err(error)? {
+        names.push(
+            entry
+                .map_err(error)?
+                .file_name()
+                .to_string_lossy()
+                .into_owned(),
+        );
+    }
+    names.sort();
+    let items = names.into_iter().map(Types::ReturnString).collect();
+    created(interpreter, Types::List(Rc::new(RefCell::new(items))))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::interpreter::{CapturedOutput, Interpreter};
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use std::io::Cursor;
+
+    fn run(interpreter: &mut Interpreter, source: &str) -> Result<(), Error> {
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens();
+        interpreter.interpret(Parser::new(tokens).parse()?)
+    }
+
+    #[test]
+    fn test_fs_natives_are_disabled_by_default() {
+        let mut interpreter = Interpreter::new();
+        match run(&mut interpreter, "read_file(\"Cargo.toml\");") {
+            Err(Error::RunTime { message, .. }) => assert_eq!(
+                message,
+                "read_file needs file system access, which is disabled (run with --allow-fs)."
+            ),
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_read_line_uses_the_interpreter_input() {
+        let output = CapturedOutput::default();
+        let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+        interpreter.capabilities.fs = true;
+        interpreter.set_input(Box::new(Cursor::new("first\r\nsecond")));
+        run(
+            &mut interpreter,
+            "print read_line(); print read_line(); print read_line();",
+        )
+        .unwrap();
+        assert_eq!(output.contents(), "first\nsecond\nnil\n");
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("rlox-io-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir
+            .join("notes.txt")
+            .display()
+            .to_string()
+            .replace('\\', "/");
+        let source = format!(
+            "write_file(\"{0}\", \"a\"); append_file(\"{0}\", \"b\");\n\
+             print read_file(\"{0}\"); print file_exists(\"{0}\"); print list_dir(\"{1}\");",
+            path,
+            dir.display().to_string().replace('\\', "/")
+        );
+
+        let output = CapturedOutput::default();
+        let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+        interpreter.capabilities.fs = true;
+        let result = run(&mut interpreter, &source);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert_eq!(output.contents(), "ab\ntrue\n[\"notes.txt\"]\n");
+    }
+}
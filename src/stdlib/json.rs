@@ -0,0 +1,491 @@
+//reading and writing JSON, objects become maps and arrays become lists
+use crate::environment::Environment;
+use crate::heap;
+use crate::interpreter::{format_number, Interpreter, Types};
+use crate::map::Map;
+use crate::stdlib::collections::new_list;
+use crate::stdlib::{created, define, define_optional, int_arg, string_arg};
+use std::cell::RefCell;
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::Chars;
+
+//deeper documents are rejected instead of overflowing the native stack
+const MAX_DEPTH: usize = 512;
+
+pub fn register(globals: &Environment) {
+    define(globals, "json_parse", 1, json_parse);
+    define_optional(globals, "json_stringify", 2, 1, json_stringify);
+}
+
+fn json_parse(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let value = parse(string_arg("json_parse", args, 0)?)?;
+    // every nested list and map is new as well
+    let mut tracer = heap::Tracer::default();
+    tracer.value(&value);
+    interpreter.charge(tracer.bytes)?;
+    Ok(value)
+}
+
+//a whole JSON document, also used by the debug adapter for its messages
+pub fn parse(source: &str) -> Result<Types, String> {
+    let mut reader = Reader::new(source);
+    reader.skip_whitespace();
+    let value = reader.value(0)?;
+    reader.skip_whitespace();
+    match reader.peek() {
+        None => Ok(value),
+        Some(c) => Err(reader.error(&format!("Unexpected '{}' after the value", c))),
+    }
+}
+
+//`indent` is the number of spaces per level, without it the output is on one line
+fn json_stringify(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let indent = match args.get(1) {
+        None | Some(Types::Nil) => None,
+        Some(_) => match int_arg("json_stringify", args, 1)? {
+            n @ 0..=16 => Some(n as usize),
+            n => return Err(format!("json_stringify indent must be between 0 and 16, got {}.", n)),
+        },
+    };
+    created(interpreter, Types::ReturnString(stringify(&args[0], indent)?))
+}
+
+pub fn stringify(value: &Types, indent: Option<usize>) -> Result<String, String> {
+    let mut writer = Writer {
+        out: String::new(),
+        indent,
+        open: Vec::new(),
+    };
+    writer.value(value)?;
+    Ok(writer.out)
+}
+
+//walks the source one character at a time, keeping the position for error messages
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            chars: source.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!(
+            "json_parse: {} at line {}, column {}.",
+            message, self.line, self.column
+        )
+    }
+
+    fn unexpected(&mut self, expected: &str) -> String {
+        match self.peek() {
+            Some(c) => self.error(&format!("Expected {} but found '{}'", expected, c)),
+            None => self.error(&format!("Expected {} but the input ended", expected)),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.advance();
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", c)))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Types, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("Nesting is too deep"));
+        }
+        match self.peek() {
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some('"') => Ok(Types::ReturnString(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some('t') => self.keyword("true", Types::Boolean(true)),
+            Some('f') => self.keyword("false", Types::Boolean(false)),
+            Some('n') => self.keyword("null", Types::Nil),
+            _ => Err(self.unexpected("a value")),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Types) -> Result<Types, String> {
+        for expected in word.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.unexpected(&format!("'{}'", word)));
+            }
+            self.advance();
+        }
+        Ok(value)
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Types, String> {
+        self.advance();
+        let mut map = Map::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.advance();
+            return Ok(Types::Map(Rc::new(RefCell::new(map))));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.unexpected("a string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.value(depth + 1)?;
+            map.insert(key, value);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.advance();
+                }
+                Some('}') => {
+                    self.advance();
+                    return Ok(Types::Map(Rc::new(RefCell::new(map))));
+                }
+                _ => return Err(self.unexpected("',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Types, String> {
+        self.advance();
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.advance();
+            return Ok(new_list(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.advance();
+                }
+                Some(']') => {
+                    self.advance();
+                    return Ok(new_list(items));
+                }
+                _ => return Err(self.unexpected("',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.advance();
+        let mut s = String::new();
+        loop {
+            match self.advance() {
+                None => return Err(self.error("Unterminated string")),
+                Some('"') => return Ok(s),
+                Some('\\') => s.push(self.escape()?),
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error("Control character in string"))
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        match self.advance() {
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('/') => Ok('/'),
+            Some('b') => Ok('\u{8}'),
+            Some('f') => Ok('\u{c}'),
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('u') => {
+                let high = self.hex4()?;
+                if !(0xD800..0xDC00).contains(&high) {
+                    return char::from_u32(high).ok_or_else(|| self.error("Invalid unicode escape"));
+                }
+                //a surrogate pair, the low half has to follow as another escape
+                if self.advance() != Some('\\') || self.advance() != Some('u') {
+                    return Err(self.error("Unpaired surrogate in unicode escape"));
+                }
+                let low = self.hex4()?;
+                if !(0xDC00..0xE000).contains(&low) {
+                    return Err(self.error("Unpaired surrogate in unicode escape"));
+                }
+                let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                char::from_u32(code).ok_or_else(|| self.error("Invalid unicode escape"))
+            }
+            _ => Err(self.error("Invalid escape in string")),
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            match self.advance().and_then(|c| c.to_digit(16)) {
+                Some(digit) => code = code * 16 + digit,
+                None => return Err(self.error("Invalid unicode escape")),
+            }
+        }
+        Ok(code)
+    }
+
+    //integers that fit become ints, everything else a float
+    fn number(&mut self) -> Result<Types, String> {
+        let mut text = String::new();
+        let mut is_float = false;
+        if self.peek() == Some('-') {
+            text.push('-');
+            self.advance();
+        }
+        match self.peek() {
+            Some('0') => {
+                text.push('0');
+                self.advance();
+            }
+            Some('1'..='9') => self.digits(&mut text),
+            _ => return Err(self.unexpected("a digit")),
+        }
+        if self.peek() == Some('.') {
+            is_float = true;
+            text.push('.');
+            self.advance();
+            if !std::matches!(self.peek(), Some('0'..='9')) {
+                return Err(self.unexpected("a digit"));
+            }
+            self.digits(&mut text);
+        }
+        if let Some(e @ ('e' | 'E')) = self.peek() {
+            is_float = true;
+            text.push(e);
+            self.advance();
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                text.push(sign);
+                self.advance();
+            }
+            if !std::matches!(self.peek(), Some('0'..='9')) {
+                return Err(self.unexpected("a digit"));
+            }
+            self.digits(&mut text);
+        }
+        if !is_float {
+            if let Ok(n) = text.parse::<i64>() {
+                return Ok(Types::Int(n));
+            }
+        }
+        text.parse::<f64>()
+            .map(Types::Number)
+            .map_err(|_| self.error("Invalid number"))
+    }
+
+    fn digits(&mut self, text: &mut String) {
+        while let Some(c @ '0'..='9') = self.peek() {
+            text.push(c);
+            self.advance();
+        }
+    }
+}
+
+struct Writer {
+    out: String,
+    indent: Option<usize>,
+    //lists and maps being written, a value inside itself cannot be encoded
+    open: Vec<*const ()>,
+}
+
+impl Writer {
+    fn value(&mut self, value: &Types) -> Result<(), String> {
+        match value {
+            Types::Nil => self.out.push_str("null"),
+            Types::Boolean(b) => self.out.push_str(&b.to_string()),
+            Types::Int(n) => self.out.push_str(&n.to_string()),
+            Types::Number(n) if n.is_finite() => self.out.push_str(&format_number(*n)),
+            Types::Number(n) => return Err(format!("json_stringify cannot encode {}.", n)),
+            Types::ReturnString(s) => self.string(s),
+            Types::List(items) => {
+                self.enter(Rc::as_ptr(items) as *const ())?;
+                let items = items.borrow();
+                self.out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    self.separator(i);
+                    self.value(item)?;
+                }
+                self.close(items.len(), ']');
+            }
+            Types::Map(map) => {
+                self.enter(Rc::as_ptr(map) as *const ())?;
+                let map = map.borrow();
+                self.out.push('{');
+                for (i, (key, item)) in map.iter().enumerate() {
+                    let key = match key {
+                        Types::ReturnString(key) => key,
+                        other => {
+                            return Err(format!("json_stringify cannot encode the map key {}.", other))
+                        }
+                    };
+                    self.separator(i);
+                    self.string(key);
+                    self.out.push_str(if self.indent.is_some() { ": " } else { ":" });
+                    self.value(item)?;
+                }
+                self.close(map.len(), '}');
+            }
+            other => {
+                let what = match other {
+                    Types::Callable(function) => format!("function '{}'", function.name()),
+                    other => other.to_string(),
+                };
+                return Err(format!("json_stringify cannot encode {}.", what));
+            }
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self, container: *const ()) -> Result<(), String> {
+        if self.open.contains(&container) {
+            return Err("json_stringify cannot encode a value that contains itself.".to_string());
+        }
+        self.open.push(container);
+        Ok(())
+    }
+
+    //the comma and line break before the item at `index`
+    fn separator(&mut self, index: usize) {
+        if index > 0 {
+            self.out.push(',');
+        }
+        self.newline(self.open.len());
+    }
+
+    fn close(&mut self, len: usize, bracket: char) {
+        self.open.pop();
+        if len > 0 {
+            self.newline(self.open.len());
+        }
+        self.out.push(bracket);
+    }
+
+    fn newline(&mut self, level: usize) {
+        if let Some(indent) = self.indent {
+            self.out.push('\n');
+            self.out.push_str(&" ".repeat(indent * level));
+        }
+    }
+
+    fn string(&mut self, s: &str) {
+        self.out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                c if (c as u32) < 0x20 => self.out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Types, String> {
+        json_parse(&mut Interpreter::new(), &vec![Types::ReturnString(source.to_string())])
+    }
+
+    fn stringify(value: Types, indent: Option<i64>) -> String {
+        let mut args = vec![value];
+        args.extend(indent.map(Types::Int));
+        match json_stringify(&mut Interpreter::new(), &args).unwrap() {
+            Types::ReturnString(s) => s,
+            other => panic!("expected a string, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_round_trips_a_document() {
+        let source = r#"{"name":"rlox","tags":["a","b\n\"c\""],"n":-12,"x":1.5e3,"ok":true,"none":null,"empty":{}}"#;
+        assert_eq!(stringify(parse(source).unwrap(), None), source.replace("1.5e3", "1500.0"));
+    }
+
+    #[test]
+    fn test_indents_nested_values() {
+        let value = parse(r#"{"a": [1, {"b": []}]}"#).unwrap();
+        assert_eq!(
+            stringify(value, Some(2)),
+            "{\n  \"a\": [\n    1,\n    {\n      \"b\": []\n    }\n  ]\n}"
+        );
+    }
+
+    #[test]
+    fn test_decodes_unicode_escapes() {
+        let value = parse(r#""\u00e9\ud83d\ude00""#).unwrap();
+        assert!(value.equals(&Types::ReturnString("é😀".to_string())));
+    }
+
+    #[test]
+    fn test_reports_the_position_of_errors() {
+        assert_eq!(
+            parse("{\n  \"a\": [1, 2,]\n}").unwrap_err(),
+            "json_parse: Expected a value but found ']' at line 2, column 14."
+        );
+        assert_eq!(
+            parse("[1, 2").unwrap_err(),
+            "json_parse: Expected ',' or ']' but the input ended at line 1, column 6."
+        );
+        assert_eq!(
+            parse("01").unwrap_err(),
+            "json_parse: Unexpected '1' after the value at line 1, column 2."
+        );
+    }
+
+    #[test]
+    fn test_rejects_values_that_contain_themselves() {
+        let list = new_list(Vec::new());
+        if let Types::List(items) = &list {
+            items.borrow_mut().push(list.clone());
+        }
+        let error = json_stringify(&mut Interpreter::new(), &vec![list.clone()]).unwrap_err();
+        assert_eq!(error, "json_stringify cannot encode a value that contains itself.");
+        //break the cycle so the test does not leak it
+        if let Types::List(items) = &list {
+            items.borrow_mut().clear();
+        }
+    }
+}
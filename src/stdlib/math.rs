@@ -0,0 +1,218 @@
+//math natives, ints stay ints where the result is exact
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, Types};
+use crate::stdlib::{define, int_arg};
+use std::f64::consts;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn register(globals: &Environment) {
+    globals.define("PI".to_string(), Some(Types::Number(consts::PI)));
+    globals.define("E".to_string(), Some(Types::Number(consts::E)));
+    define(globals, "sqrt", 1, sqrt);
+    define(globals, "pow", 2, pow);
+    define(globals, "abs", 1, abs);
+    define(globals, "floor", 1, floor);
+    define(globals, "ceil", 1, ceil);
+    define(globals, "round", 1, round);
+    define(globals, "min", 2, min);
+    define(globals, "max", 2, max);
+    define(globals, "sin", 1, sin);
+    define(globals, "cos", 1, cos);
+    define(globals, "tan", 1, tan);
+    define(globals, "log", 1, log);
+    define(globals, "exp", 1, exp);
+    define(globals, "random", 0, random);
+    define(globals, "random_int", 2, random_int);
+    define(globals, "seed_random", 1, seed_random);
+}
+
+//splitmix64, small and good enough for scripts, not for anything secret
+#[derive(Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    //seeded from the clock, `seed_random(n)` makes a run reproducible
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    //uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    //uniform in [lo, hi], both ends included
+    pub fn range(&mut self, lo: i64, hi: i64) -> i64 {
+        let span = (hi as i128 - lo as i128 + 1) as u128;
+        let offset = (self.next_u64() as u128 * span) >> 64;
+        (lo as i128 + offset as i128) as i64
+    }
+}
+
+fn number_arg(name: &str, args: &[Types], index: usize) -> Result<f64, String> {
+    match &args[index] {
+        Types::Int(n) => Ok(*n as f64),
+        Types::Number(n) => Ok(*n),
+        other => Err(format!(
+            "{} expects a number as argument {}, got {}.",
+            name,
+            index + 1,
+            other
+        )),
+    }
+}
+
+//floats that do not fit an int are an error instead of saturating
+fn to_int(name: &str, n: f64) -> Result<Types, String> {
+    if n.is_finite() && n.abs() < 9.2e18 {
+        Ok(Types::Int(n as i64))
+    } else {
+        Err(format!("{} result {} does not fit in an integer.", name, n))
+    }
+}
+
+fn float(name: &str, args: &[Types], f: fn(f64) -> f64) -> Result<Types, String> {
+    Ok(Types::Number(f(number_arg(name, args, 0)?)))
+}
+
+fn sqrt(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    float("sqrt", args, f64::sqrt)
+}
+
+fn pow(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    match (&args[0], &args[1]) {
+        (Types::Int(base), Types::Int(exponent)) if *exponent >= 0 => u32::try_from(*exponent)
+            .ok()
+            .and_then(|exponent| base.checked_pow(exponent))
+            .map(Types::Int)
+            .ok_or_else(|| "Integer overflow.".to_string()),
+        _ => {
+            let base = number_arg("pow", args, 0)?;
+            Ok(Types::Number(base.powf(number_arg("pow", args, 1)?)))
+        }
+    }
+}
+
+fn abs(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    match &args[0] {
+        Types::Int(n) => n
+            .checked_abs()
+            .map(Types::Int)
+            .ok_or_else(|| "Integer overflow.".to_string()),
+        _ => float("abs", args, f64::abs),
+    }
+}
+
+//floor, ceil and round give ints so the result can be used as an index
+fn floor(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    to_int("floor", number_arg("floor", args, 0)?.floor())
+}
+
+fn ceil(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    to_int("ceil", number_arg("ceil", args, 0)?.ceil())
+}
+
+fn round(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    to_int("round", number_arg("round", args, 0)?.round())
+}
+
+//the smaller argument itself, so two ints give an int
+fn min(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let (a, b) = (number_arg("min", args, 0)?, number_arg("min", args, 1)?);
+    Ok(if b < a {
+        args[1].clone()
+    } else {
+        args[0].clone()
+    })
+}
+
+fn max(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let (a, b) = (number_arg("max", args, 0)?, number_arg("max", args, 1)?);
+    Ok(if b > a {
+        args[1].clone()
+    } else {
+        args[0].clone()
+    })
+}
+
+fn sin(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    float("sin", args, f64::sin)
+}
+
+fn cos(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    float("cos", args, f64::cos)
+}
+
+fn tan(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    float("tan", args, f64::tan)
+}
+
+//natural logarithm
+fn log(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    float("log", args, f64::ln)
+}
+
+fn exp(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    float("exp", args, f64::exp)
+}
+
+fn random(interpreter: &mut Interpreter, _: &Vec<Types>) -> Result<Types, String> {
+    Ok(Types::Number(interpreter.random.next_f64()))
+}
+
+fn random_int(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let lo = int_arg("random_int", args, 0)?;
+    let hi = int_arg("random_int", args, 1)?;
+    if lo > hi {
+        return Err(format!("random_int range {}..{} is empty.", lo, hi));
+    }
+    Ok(Types::Int(interpreter.random.range(lo, hi)))
+}
+
+fn seed_random(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    interpreter.random = Random::new(int_arg("seed_random", args, 0)? as u64);
+    Ok(Types::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Random;
+    use crate::interpreter::run_captured;
+
+    #[test]
+    fn test_seeded_random_is_reproducible() {
+        let script = "seed_random(7); print random(); print random_int(1, 6);";
+        assert_eq!(run_captured(script).unwrap(), run_captured(script).unwrap());
+    }
+
+    #[test]
+    fn test_random_range_stays_in_bounds() {
+        let mut random = Random::new(1);
+        for _ in 0..1000 {
+            let n = random.range(-3, 3);
+            assert!((-3..=3).contains(&n));
+            let f = random.next_f64();
+            assert!((0.0..1.0).contains(&f));
+        }
+        assert_eq!(random.range(i64::MIN, i64::MIN), i64::MIN);
+        random.range(i64::MIN, i64::MAX);
+    }
+}
@@ -0,0 +1,93 @@
+//natives shipped with the interpreter, each submodule defines its functions as globals
+pub mod collections;
+pub mod io;
+pub mod json;
+pub mod math;
+pub mod process;
+pub mod runtime;
+pub mod strings;
+pub mod types;
+
+use crate::environment::Environment;
+use crate::function::{NativeFn, NativeFunction};
+use crate::heap;
+use crate::interpreter::{Interpreter, Types};
+use std::rc::Rc;
+
+//defines every standard library function in `globals`
+pub fn register(globals: &Environment) {
+    collections::register(globals);
+    io::register(globals);
+    json::register(globals);
+    math::register(globals);
+    process::register(globals);
+    runtime::register(globals);
+    strings::register(globals);
+    types::register(globals);
+}
+
+//defines a rust function as the global `name`
+pub fn define(globals: &Environment, name: &str, arity: usize, body: NativeFn) {
+    define_optional(globals, name, arity, 0, body);
+}
+
+//like `define` but the last `optional` parameters may be left out, `args` is then shorter
+pub fn define_optional(
+    globals: &Environment,
+    name: &str,
+    arity: usize,
+    optional: usize,
+    body: NativeFn,
+) {
+    let function = NativeFunction {
+        name: name.to_string(),
+        arity,
+        optional,
+        body: Box::new(body),
+    };
+    globals.define(
+        name.to_string(),
+        Some(Types::Callable(Rc::new(Box::new(function)))),
+    );
+}
+
+//natives check their own arguments, a wrong type becomes a runtime error at the call
+pub fn string_arg<'a>(name: &str, args: &'a [Types], index: usize) -> Result<&'a str, String> {
+    match &args[index] {
+        Types::ReturnString(s) => Ok(s),
+        other => Err(format!(
+            "{} expects a string as argument {}, got {}.",
+            name,
+            index + 1,
+            other
+        )),
+    }
+}
+
+pub fn int_arg(name: &str, args: &[Types], index: usize) -> Result<i64, String> {
+    match &args[index] {
+        Types::Int(n) => Ok(*n),
+        Types::Number(n) if n.fract() == 0.0 && n.abs() < 9.2e18 => Ok(*n as i64),
+        other => Err(format!(
+            "{} expects an integer as argument {}, got {}.",
+            name,
+            index + 1,
+            other
+        )),
+    }
+}
+
+//the text of a value as `print` shows it, strings without their quotes
+pub fn text(value: &Types) -> String {
+    match value {
+        Types::ReturnString(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+//charges a value a native has just built against the heap limit, values it only hands back
+//from its arguments were charged when they were made
+pub fn created(interpreter: &mut Interpreter, value: Types) -> Result<Types, String> {
+    interpreter.charge(heap::shallow_size(&value))?;
+    Ok(value)
+}
@@ -0,0 +1,82 @@
+//the process a script runs in: its arguments, environment and exit status
+use crate::environment::Environment;
+use crate::error::Error;
+use crate::function::Callable;
+use crate::interpreter::{Interpreter, Types};
+use crate::stdlib::{created, define, int_arg, string_arg};
+use crate::token::Token;
+use std::cell::RefCell;
+use std::env;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::rc::Rc;
+
+pub fn register(globals: &Environment) {
+    define(globals, "args", 0, args);
+    define(globals, "getenv", 1, getenv);
+    globals.define(
+        "exit".to_string(),
+        Some(Types::Callable(Rc::new(Box::new(Exit)))),
+    );
+}
+
+fn args(interpreter: &mut Interpreter, _: &Vec<Types>) -> Result<Types, String> {
+    let items = interpreter
+        .args()
+        .iter()
+        .map(|arg| Types::ReturnString(arg.clone()))
+        .collect();
+    created(interpreter, Types::List(Rc::new(RefCell::new(items))))
+}
+
+//nil when the variable is not set
+fn getenv(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    if !interpreter.capabilities.env {
+        return Err("getenv needs environment access, which is disabled.".to_string());
+    }
+    let name = string_arg("getenv", args, 0)?;
+    match env::var(name) {
+        Ok(value) => created(interpreter, Types::ReturnString(value)),
+        Err(_) => Ok(Types::Nil),
+    }
+}
+
+//`exit(code)` unwinds with Error::Exit instead of ending the process from inside a call,
+//so finally blocks run and the host decides what exiting means
+struct Exit;
+
+impl Callable for Exit {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> String {
+        "exit".to_string()
+    }
+
+    fn call(&self, _: &mut Interpreter, paren: &Token, args: &Vec<Types>) -> Result<Types, Error> {
+        let runtime_error = |message: String| Error::RunTime {
+            token: paren.clone(),
+            message,
+        };
+        let code = int_arg("exit", args, 0).map_err(runtime_error)?;
+        match i32::try_from(code) {
+            Ok(code) => Err(Error::Exit { code }),
+            Err(_) => Err(runtime_error(format!(
+                "exit code {} is out of range.",
+                code
+            ))),
+        }
+    }
+}
+
+impl Debug for Exit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "<native func>")
+    }
+}
+
+impl Display for Exit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "<native func>")
+    }
+}
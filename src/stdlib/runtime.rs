@@ -0,0 +1,33 @@
+//diagnostics about the interpreter running the script
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, Types};
+use crate::map::Map;
+use crate::stdlib::{created, define};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub fn register(globals: &Environment) {
+    define(globals, "mem_usage", 0, mem_usage);
+    define(globals, "gc", 0, gc);
+}
+
+//approximate bytes held by the values the script can still reach
+fn mem_usage(interpreter: &mut Interpreter, _: &Vec<Types>) -> Result<Types, String> {
+    Ok(Types::Int(interpreter.measure_heap() as i64))
+}
+
+//runs the cycle collector now and returns its statistics as a map
+fn gc(interpreter: &mut Interpreter, _: &Vec<Types>) -> Result<Types, String> {
+    interpreter.collect_garbage();
+    let stats = interpreter.gc_stats();
+    let mut map = Map::new();
+    for (key, value) in [
+        ("collections", stats.collections),
+        ("freed", stats.freed),
+        ("last_freed", stats.last_freed),
+        ("live", stats.live),
+    ] {
+        map.insert(key.to_string(), Types::Int(value as i64));
+    }
+    created(interpreter, Types::Map(Rc::new(RefCell::new(map))))
+}
@@ -0,0 +1,234 @@
+//string natives, indices count characters rather than bytes
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, Types};
+use crate::stdlib::{created, define, int_arg, string_arg, text};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub fn register(globals: &Environment) {
+    define(globals, "len", 1, len);
+    define(globals, "substr", 3, substr);
+    define(globals, "upper", 1, upper);
+    define(globals, "lower", 1, lower);
+    define(globals, "trim", 1, trim);
+    define(globals, "split", 2, split);
+    define(globals, "join", 2, join);
+    define(globals, "replace", 3, replace);
+    define(globals, "find", 2, find);
+    define(globals, "starts_with", 2, starts_with);
+    define(globals, "ends_with", 2, ends_with);
+    define(globals, "repeat", 2, repeat);
+    define(globals, "char_at", 2, char_at);
+    define(globals, "ord", 1, ord);
+    define(globals, "chr", 1, chr);
+}
+
+fn string(interpreter: &mut Interpreter, s: String) -> Result<Types, String> {
+    created(interpreter, Types::ReturnString(s))
+}
+
+//characters of a string, items of a list or entries of a map
+fn len(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    match &args[0] {
+        Types::ReturnString(s) => Ok(Types::Int(s.chars().count() as i64)),
+        Types::List(items) => Ok(Types::Int(items.borrow().len() as i64)),
+        Types::Map(map) => Ok(Types::Int(map.borrow().len() as i64)),
+        other => Err(format!("len expects a string, a list or a map, got {}.", other)),
+    }
+}
+
+//the characters from `start` up to, but not including, `end`
+fn substr(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let s = string_arg("substr", args, 0)?;
+    let start = int_arg("substr", args, 1)?;
+    let end = int_arg("substr", args, 2)?;
+    let length = s.chars().count() as i64;
+    if start < 0 || end < start || end > length {
+        return Err(format!(
+            "substr range {}..{} is out of bounds for a string of length {}.",
+            start, end, length
+        ));
+    }
+    string(
+        interpreter,
+        s.chars()
+            .skip(start as usize)
+            .take((end - start) as usize)
+            .collect(),
+    )
+}
+
+fn upper(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    string(interpreter, string_arg("upper", args, 0)?.to_uppercase())
+}
+
+fn lower(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    string(interpreter, string_arg("lower", args, 0)?.to_lowercase())
+}
+
+fn trim(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    string(interpreter, string_arg("trim", args, 0)?.trim().to_string())
+}
+
+//an empty separator splits into single characters
+fn split(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let s = string_arg("split", args, 0)?;
+    let separator = string_arg("split", args, 1)?;
+    let parts: Vec<Types> = if separator.is_empty() {
+        s.chars()
+            .map(|c| Types::ReturnString(c.to_string()))
+            .collect()
+    } else {
+        s.split(separator)
+            .map(|part| Types::ReturnString(part.to_string()))
+            .collect()
+    };
+    created(interpreter, Types::List(Rc::new(RefCell::new(parts))))
+}
+
+//items that are not strings are joined the way `print` shows them
+fn join(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let separator = string_arg("join", args, 1)?;
+    match &args[0] {
+        Types::List(items) => {
+            let parts: Vec<String> = items.borrow().iter().map(text).collect();
+            string(interpreter, parts.join(separator))
+        }
+        other => Err(format!("join expects a list as argument 1, got {}.", other)),
+    }
+}
+
+fn replace(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let s = string_arg("replace", args, 0)?;
+    let from = string_arg("replace", args, 1)?;
+    let to = string_arg("replace", args, 2)?;
+    if from.is_empty() {
+        return Err("replace cannot search for an empty string.".to_string());
+    }
+    string(interpreter, s.replace(from, to))
+}
+
+//index of the first occurrence, -1 when there is none
+fn find(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let s = string_arg("find", args, 0)?;
+    let needle = string_arg("find", args, 1)?;
+    Ok(Types::Int(match s.find(needle) {
+        Some(byte_index) => s[..byte_index].chars().count() as i64,
+        None => -1,
+    }))
+}
+
+fn starts_with(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let s = string_arg("starts_with", args, 0)?;
+    let prefix = string_arg("starts_with", args, 1)?;
+    Ok(Types::Boolean(s.starts_with(prefix)))
+}
+
+fn ends_with(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let s = string_arg("ends_with", args, 0)?;
+    let suffix = string_arg("ends_with", args, 1)?;
+    Ok(Types::Boolean(s.ends_with(suffix)))
+}
+
+fn repeat(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let s = string_arg("repeat", args, 0)?;
+    let count = match int_arg("repeat", args, 1)? {
+        count if count < 0 => {
+            return Err(format!("repeat count cannot be negative, got {}.", count))
+        }
+        count => count as usize,
+    };
+    let bytes = s
+        .len()
+        .checked_mul(count)
+        .filter(|&bytes| bytes <= isize::MAX as usize)
+        .ok_or_else(|| format!("repeat of {} copies is too large.", count))?;
+    // the heap limit is checked before the string exists, it is charged once built
+    interpreter.reserve(bytes)?;
+    string(interpreter, s.repeat(count))
+}
+
+fn char_at(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let s = string_arg("char_at", args, 0)?;
+    let index = int_arg("char_at", args, 1)?;
+    let c = if index < 0 {
+        None
+    } else {
+        s.chars().nth(index as usize)
+    };
+    match c {
+        Some(c) => string(interpreter, c.to_string()),
+        None => Err(format!(
+            "char_at index {} is out of bounds for a string of length {}.",
+            index,
+            s.chars().count()
+        )),
+    }
+}
+
+//the unicode code point of a single character string
+fn ord(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let s = string_arg("ord", args, 0)?;
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Types::Int(c as i64)),
+        _ => Err(format!("ord expects a single character, got \"{}\".", s)),
+    }
+}
+
+fn chr(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let code = int_arg("chr", args, 0)?;
+    match u32::try_from(code).ok().and_then(char::from_u32) {
+        Some(c) => string(interpreter, c.to_string()),
+        None => Err(format!("chr expects a valid code point, got {}.", code)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::interpreter::run_captured;
+
+    fn runtime_error(source: &str) -> String {
+        match run_captured(source) {
+            Err(Error::RunTime { message, .. }) => message,
+            other => panic!("expected a runtime error from {}, got {:?}", source, other),
+        }
+    }
+
+    #[test]
+    fn test_bad_arguments_are_runtime_errors() {
+        assert_eq!(
+            runtime_error("len(nil);"),
+            "len expects a string, a list or a map, got nil."
+        );
+        assert_eq!(
+            runtime_error("substr(\"abc\", 1, 9);"),
+            "substr range 1..9 is out of bounds for a string of length 3."
+        );
+        assert_eq!(
+            runtime_error("repeat(\"a\", \"b\");"),
+            "repeat expects an integer as argument 2, got \"b\"."
+        );
+        assert_eq!(
+            runtime_error("repeat(\"ab\", 9223372036854775807);"),
+            "repeat of 9223372036854775807 copies is too large."
+        );
+        assert_eq!(
+            runtime_error("char_at(\"\", 0);"),
+            "char_at index 0 is out of bounds for a string of length 0."
+        );
+        assert_eq!(
+            runtime_error("ord(\"ab\");"),
+            "ord expects a single character, got \"ab\"."
+        );
+        assert_eq!(
+            runtime_error("chr(-1);"),
+            "chr expects a valid code point, got -1."
+        );
+        assert_eq!(
+            runtime_error("join(\"abc\", \",\");"),
+            "join expects a list as argument 1, got \"abc\"."
+        );
+    }
+}
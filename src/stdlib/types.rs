@@ -0,0 +1,59 @@
+//introspection and conversion natives
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, Types};
+use crate::stdlib::{created, define};
+
+pub fn register(globals: &Environment) {
+    define(globals, "type_of", 1, type_of);
+    define(globals, "str", 1, str);
+    define(globals, "num", 1, num);
+    define(globals, "is_callable", 1, is_callable);
+}
+
+//ints and floats are both numbers, `int(x) == x` tells them apart
+fn type_of(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    created(interpreter, Types::ReturnString(type_name(&args[0]).to_string()))
+}
+
+pub fn type_name(value: &Types) -> &'static str {
+    match value {
+        Types::Number(_) | Types::Int(_) => "number",
+        Types::ReturnString(_) => "string",
+        Types::Boolean(_) => "bool",
+        Types::Nil => "nil",
+        Types::Callable(_) => "function",
+        Types::Class(_) => "class",
+        Types::Module(_) => "module",
+        Types::List(_) => "list",
+        Types::Map(_) => "map",
+        Types::Error(_) => "error",
+    }
+}
+
+//the text `print` would show
+fn str(interpreter: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    let text = interpreter.stringify(args[0].clone());
+    created(interpreter, Types::ReturnString(text))
+}
+
+//"42" becomes an int and "4.2" a float, numbers are returned unchanged
+fn num(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    match &args[0] {
+        Types::ReturnString(s) => {
+            let trimmed = s.trim();
+            if let Ok(n) = trimmed.parse::<i64>() {
+                return Ok(Types::Int(n));
+            }
+            match trimmed.parse::<f64>() {
+                Ok(n) if n.is_finite() => Ok(Types::Number(n)),
+                _ => Err(format!("num cannot parse \"{}\" as a number.", s)),
+            }
+        }
+        number @ (Types::Int(_) | Types::Number(_)) => Ok(number.clone()),
+        other => Err(format!("num expects a string, got {}.", other)),
+    }
+}
+
+fn is_callable(_: &mut Interpreter, args: &Vec<Types>) -> Result<Types, String> {
+    Ok(Types::Boolean(matches!(args[0], Types::Callable(_))))
+}
@@ -0,0 +1,138 @@
+use crate::error::Error;
+use crate::interpreter::{Capabilities, Interpreter};
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+//Pass and fail counts of an `rlox test` run
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+//runs the `test "name" { }` blocks of every .lox file under `path`
+//a file that fails to parse or errors outside of a test counts as one failure
+pub fn run_tests(path: &Path, capabilities: Capabilities) -> Summary {
+    let mut files = Vec::new();
+    collect_lox_files(path, &mut files);
+    files.sort();
+
+    let mut summary = Summary::default();
+    for file in files {
+        run_file_tests(&file, capabilities, &mut summary);
+    }
+    println!("\n{} passed, {} failed", summary.passed, summary.failed);
+    summary
+}
+
+fn collect_lox_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        if let Ok(entries) = fs::read_dir(path) {
+            for entry in entries.flatten() {
+                collect_lox_files(&entry.path(), files);
+            }
+        }
+    } else if path.extension().is_some_and(|ext| ext == "lox") {
+        files.push(path.to_path_buf());
+    }
+}
+
+fn run_file_tests(path: &Path, capabilities: Capabilities, summary: &mut Summary) {
+    println!("{}", path.display());
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            println!("  FAIL {}", e);
+            summary.failed += 1;
+            return;
+        }
+    };
+
+    let mut scanner = Scanner::new(source.as_bytes());
+    let tokens = scanner.scan_tokens();
+    let mut parser = Parser::new(tokens);
+    let statements = match parser.parse() {
+        Ok(statements) if !scanner.had_error() && !parser.had_error() => statements,
+        _ => {
+            println!("  FAIL could not be parsed");
+            summary.failed += 1;
+            return;
+        }
+    };
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_script_path(path);
+    interpreter.capabilities = capabilities;
+    let mut resolver = Resolver::new(&mut interpreter);
+    resolver.resolve_stmts(&statements);
+    if resolver.had_error() {
+        println!("  FAIL could not be resolved");
+        summary.failed += 1;
+        return;
+    }
+    if let Err(e) = interpreter.interpret(statements) {
+        println!("  FAIL {}", describe(&e));
+        summary.failed += 1;
+        return;
+    }
+
+    for test in interpreter.take_tests() {
+        match interpreter.run_test(&test) {
+            Ok(()) => {
+                println!("  PASS {}", test.name);
+                summary.passed += 1;
+            }
+            Err(e) => {
+                println!("  FAIL {}: {}", test.name, describe(&e));
+                summary.failed += 1;
+            }
+        }
+    }
+}
+
+fn describe(error: &Error) -> String {
+    match error {
+        Error::RunTime { token, message } => format!("{} [line {}]", message, token.line),
+        Error::Throw { token, .. } => format!("{} [line {}]", error, token.line),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_passing_and_failing_tests() {
+        let dir = std::env::temp_dir().join(format!("rlox-test-runner-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(
+            dir.join("math.lox"),
+            "fun add(a, b) { return a + b; }\n\
+             test \"adds\" { assert_eq(add(1, 2), 3); }\n\
+             test \"fails\" { assert_eq(add(1, 2), 4); }\n\
+             test \"raises\" { fun boom() { return 1 / 0; } assert_raises(boom); }\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("nested").join("truth.lox"),
+            "test \"truthy\" { assert_true(1); }\ntest \"falsy\" { assert_true(nil); }\n",
+        )
+        .unwrap();
+        fs::write(dir.join("notes.txt"), "test \"ignored\" { assert_true(false); }").unwrap();
+        // resolved with an error, its test never runs
+        fs::write(
+            dir.join("unresolved.lox"),
+            "{ var a = 1; var a = 2; }\ntest \"skipped\" { assert_true(true); }\n",
+        )
+        .unwrap();
+
+        let summary = run_tests(&dir, Capabilities::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(summary, Summary { passed: 3, failed: 3 });
+    }
+}
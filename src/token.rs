@@ -7,21 +7,25 @@ pub enum TokenType {
     // Single-Char tokens
     LeftParen, RightParen, LeftBrace,
     RightBrace, Comma,Colon,
-    Dot, Minus, Plus, SemiColon, Slash, Star,
+    Dot, Minus, Percent, Plus, SemiColon, Slash, Star,
+    Ampersand, Pipe, Caret, Tilde, Question,
 
     //One or Two Tokens
     Bang, BangEqual,Equal,
     EqualEqual, GreaterEqual, Greater, LessEqual, Less,
+    StarStar, LessLess, GreaterGreater,
+    PlusEqual, MinusEqual, StarEqual, SlashEqual, PercentEqual,
+    QuestionQuestion, QuestionDot,
 
     //Literals
-    Identifier(String), String(String), Number(f64),
+    Identifier(String), String(String), Number(f64), Int(i64),
 
     //Keywords
     And, Class, Else, False, Fun, For, If, Nil, Or,
-    Print,Return, Super, This, True, Var, While, Break,
+    Print,Return, Super, This, True, Var, While, Break, Import,
+    Throw, Try, Catch, Finally,
     //Comment
-
-    DoubleSlash,
+    DocComment(String),
     //End Of File
     EOF,
 
@@ -38,10 +42,18 @@ impl Display for TokenType {
             TokenType::Comma => f.write_str(","),
             TokenType::Dot => f.write_str("."),
             TokenType::Minus => f.write_str("-"),
+            TokenType::Percent => f.write_str("%"),
             TokenType::Plus => f.write_str("+"),
             TokenType::SemiColon => f.write_str(";"),
             TokenType::Slash => f.write_str("/"),
             TokenType::Star => f.write_str("*"),
+            TokenType::Ampersand => f.write_str("&"),
+            TokenType::Pipe => f.write_str("|"),
+            TokenType::Caret => f.write_str("^"),
+            TokenType::Tilde => f.write_str("~"),
+            TokenType::Question => f.write_str("?"),
+            TokenType::QuestionQuestion => f.write_str("??"),
+            TokenType::QuestionDot => f.write_str("?."),
             TokenType::Bang => f.write_str("!"),
             TokenType::BangEqual => f.write_str("!="),
             TokenType::Equal => f.write_str("="),
@@ -50,12 +62,26 @@ impl Display for TokenType {
             TokenType::GreaterEqual => f.write_str(">="),
             TokenType::Less => f.write_str("<"),
             TokenType::LessEqual => f.write_str("<="),
+            TokenType::StarStar => f.write_str("**"),
+            TokenType::LessLess => f.write_str("<<"),
+            TokenType::GreaterGreater => f.write_str(">>"),
+            TokenType::PlusEqual => f.write_str("+="),
+            TokenType::MinusEqual => f.write_str("-="),
+            TokenType::StarEqual => f.write_str("*="),
+            TokenType::SlashEqual => f.write_str("/="),
+            TokenType::PercentEqual => f.write_str("%="),
             TokenType::Identifier(s) => f.write_str(s),
             TokenType::String(s) => s.fmt(f),
             TokenType::Number(n) => n.fmt(f),
+            TokenType::Int(n) => n.fmt(f),
             TokenType::And => f.write_str("and"),
             TokenType::Break => f.write_str("break"),
+            TokenType::Throw => f.write_str("throw"),
+            TokenType::Try => f.write_str("try"),
+            TokenType::Catch => f.write_str("catch"),
+            TokenType::Finally => f.write_str("finally"),
             TokenType::Class => f.write_str("class"),
+            TokenType::Import => f.write_str("import"),
             TokenType::Else => f.write_str("else"),
             TokenType::False => f.write_str("false"),
             TokenType::Fun => f.write_str("fun"),
@@ -70,7 +96,7 @@ impl Display for TokenType {
             TokenType::True => f.write_str("true"),
             TokenType::Var => f.write_str("var"),
             TokenType::While => f.write_str("while"),
-            TokenType::DoubleSlash=> f.write_str(""),
+            TokenType::DocComment(s) => write!(f, "/// {}", s),
             TokenType::EOF => f.write_str("\\d"),
         }
     }
@@ -78,11 +104,22 @@ impl Display for TokenType {
 
 //Token struct to present a token
 //lexeme might be used for identifier I guess
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Token {
     pub t_type: TokenType,
     pub lexeme: String,
     pub line: u32,
+    // 1-based, where the token starts on its line, 0 for tokens made up by the interpreter
+    pub column: u32,
+}
+
+//A comment kept aside by the scanner with its delimiters, `block` is the id of the
+//innermost `{` it sits in (0 for top level) so it can be put back in place
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub line: u32,
+    pub block: usize,
+    pub text: String,
 }
 
 impl Display for Token {
@@ -95,6 +132,17 @@ impl  Hash for Token {
     fn hash<H: Hasher>(&self, state:&mut H){
         self.lexeme.hash(state);
         self.line.hash(state);
+        self.column.hash(state);
+    }
+}
+
+//the resolver keys its table by token, so two uses of a name on one line differ by column
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.t_type == other.t_type
+            && self.lexeme == other.lexeme
+            && self.line == other.line
+            && self.column == other.column
     }
 }
 
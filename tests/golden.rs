@@ -0,0 +1,173 @@
+//runs every .lox file under tests/lox through the interpreter binary and checks its
+//output against the annotations written in the file, in the style of the crafting
+//interpreters test suite:
+//
+//  print 1 + 2;              // expect: 3
+//  print nil + 1;            // expect runtime error: Operands must be two numbers or two strings
+//  var a = ;                 // [line 3] Error at ';': Expect expression.
+//  return 1;                 // Error at 'return': Cannot return from top-level code.
+//
+//a compile error without a `[line N]` prefix is expected on the line it is written on,
+//`// flags: --allow-fs` passes extra command line flags to the interpreter, `// args: a b`
+//passes arguments to the script itself, `// expect exit: 3` expects `exit(3)` and
+//files in a `fixtures` directory are only there to be imported and are not run themselves
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Default)]
+struct Expectations {
+    flags: Vec<String>,
+    args: Vec<String>,
+    exit: Option<i32>,
+    output: Vec<String>,
+    compile_errors: Vec<String>,
+    runtime_error: Option<(String, usize)>,
+}
+
+impl Expectations {
+    fn parse(source: &str) -> Self {
+        let mut expectations = Expectations::default();
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let comment = match line.find("// ") {
+                Some(start) => &line[start + 3..],
+                None => continue,
+            };
+            if let Some(flags) = comment.strip_prefix("flags: ") {
+                expectations
+                    .flags
+                    .extend(flags.split_whitespace().map(String::from));
+            } else if let Some(args) = comment.strip_prefix("args: ") {
+                expectations
+                    .args
+                    .extend(args.split_whitespace().map(String::from));
+            } else if let Some(code) = comment.strip_prefix("expect exit: ") {
+                expectations.exit = code.trim().parse().ok();
+            } else if let Some(text) = comment.strip_prefix("expect: ") {
+                expectations.output.push(text.to_string());
+            } else if let Some(text) = comment.strip_prefix("expect runtime error: ") {
+                expectations.runtime_error = Some((text.to_string(), line_number));
+            } else if comment.starts_with("[line ") {
+                expectations.compile_errors.push(comment.to_string());
+            } else if comment.starts_with("Error") {
+                expectations
+                    .compile_errors
+                    .push(format!("[line {}] {}", line_number, comment));
+            }
+        }
+        expectations
+    }
+
+    fn exit_code(&self) -> i32 {
+        if let Some(code) = self.exit {
+            code
+        } else if !self.compile_errors.is_empty() {
+            65
+        } else if self.runtime_error.is_some() {
+            70
+        } else {
+            0
+        }
+    }
+}
+
+fn collect_lox_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).expect("tests/lox should exist").flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if !path.ends_with("fixtures") {
+                collect_lox_files(&path, files);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            files.push(path);
+        }
+    }
+}
+
+//returns a description of every mismatch, empty when the file behaves as annotated
+fn check_file(path: &Path) -> Vec<String> {
+    let source = fs::read_to_string(path).unwrap();
+    let expected = Expectations::parse(&source);
+    let output = Command::new(env!("CARGO_BIN_EXE_Lox"))
+        .args(&expected.flags)
+        .arg("run")
+        .arg(path)
+        .args(&expected.args)
+        .output()
+        .expect("could not start the interpreter");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout: Vec<&str> = stdout.lines().collect();
+    let stderr: Vec<&str> = stderr.lines().collect();
+    let mut failures = Vec::new();
+
+    for (index, expected_line) in expected.output.iter().enumerate() {
+        match stdout.get(index) {
+            Some(line) if line == expected_line => {}
+            Some(line) => failures.push(format!(
+                "expected output '{}', got '{}'",
+                expected_line, line
+            )),
+            None => failures.push(format!("missing expected output '{}'", expected_line)),
+        }
+    }
+    for line in stdout.iter().skip(expected.output.len()) {
+        failures.push(format!("unexpected output '{}'", line));
+    }
+
+    if !expected.compile_errors.is_empty() {
+        for error in &expected.compile_errors {
+            if !stderr.contains(&error.as_str()) {
+                failures.push(format!("missing expected error '{}'", error));
+            }
+        }
+        for line in &stderr {
+            if !expected.compile_errors.iter().any(|error| error == line) {
+                failures.push(format!("unexpected error '{}'", line));
+            }
+        }
+    } else if let Some((message, line)) = &expected.runtime_error {
+        let trace = format!("[line {}]", line);
+        if stderr.first() != Some(&message.as_str()) || stderr.get(1) != Some(&trace.as_str()) {
+            failures.push(format!(
+                "expected runtime error '{}' {}, got '{}'",
+                message,
+                trace,
+                stderr.join("\n")
+            ));
+        }
+    } else if !stderr.is_empty() {
+        failures.push(format!("unexpected error output '{}'", stderr.join("\n")));
+    }
+
+    let code = output.status.code().unwrap_or(-1);
+    if code != expected.exit_code() {
+        failures.push(format!(
+            "expected exit code {}, got {}",
+            expected.exit_code(),
+            code
+        ));
+    }
+    failures
+}
+
+#[test]
+fn lox_files_match_their_annotations() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("lox");
+    let mut files = Vec::new();
+    collect_lox_files(&root, &mut files);
+    files.sort();
+    assert!(!files.is_empty(), "no .lox files found in {}", root.display());
+
+    let mut report = String::new();
+    for file in &files {
+        let failures = check_file(file);
+        if !failures.is_empty() {
+            let name = file.strip_prefix(&root).unwrap_or(file);
+            report.push_str(&format!("\n{}:\n  {}", name.display(), failures.join("\n  ")));
+        }
+    }
+    assert!(report.is_empty(), "golden files failed:{}", report);
+}